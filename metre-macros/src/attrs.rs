@@ -1,8 +1,8 @@
 #![allow(clippy::manual_unwrap_or_default)]
 // darling macro warns without this
 
-use darling::util::SpannedValue;
-use darling::FromAttributes;
+use darling::util::{PathList, SpannedValue};
+use darling::{FromAttributes, FromMeta};
 use inflector::Inflector;
 use proc_macro2::Ident;
 use syn::{Expr, ExprPath, Meta, Path};
@@ -20,6 +20,45 @@ pub enum Inflection {
 }
 
 impl Inflection {
+  /// Rename a field like `#[serde(rename_all)]` does, this must match serde exactly
+  /// because the partial struct is renamed by serde and this is used for the property names
+  ///
+  /// serde expects snake_case field names and only changes the case of the letters and the `_` separators,
+  /// unlike [`Self::apply`] which splits the words with Inflector, eg: `http2_port` is not changed to `http_2_port`
+  pub fn apply_to_field(self, field: &str) -> String {
+    use Inflection::*;
+    match self {
+      Lower | Snake => field.to_string(),
+      Upper | UpperSnake => field.to_ascii_uppercase(),
+      Pascal => {
+        let mut pascal = String::with_capacity(field.len());
+        let mut capitalize = true;
+        for c in field.chars() {
+          if c == '_' {
+            capitalize = true;
+          } else if capitalize {
+            pascal.push(c.to_ascii_uppercase());
+            capitalize = false;
+          } else {
+            pascal.push(c);
+          }
+        }
+        pascal
+      }
+      Camel => {
+        let pascal = Pascal.apply_to_field(field);
+        let mut chars = pascal.chars();
+        match chars.next() {
+          Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+          None => pascal,
+        }
+      }
+      Kebab => field.replace('_', "-"),
+      UpperKebab => field.to_ascii_uppercase().replace('_', "-"),
+    }
+  }
+
+  /// Rename an env key, the words are splitted with Inflector
   pub fn apply(self, src: &str) -> String {
     use Inflection::*;
     match self {
@@ -55,11 +94,50 @@ impl std::str::FromStr for Inflection {
   }
 }
 
+/// The value of the field `env` attribute
+///
+/// `env = "{}KEY"` sets the env key and `env = false` is a shorthand for `skip_env`
+#[derive(Debug, Clone)]
+pub enum EnvArg {
+  Key(String),
+  Enabled(bool),
+}
+
+impl FromMeta for EnvArg {
+  fn from_string(value: &str) -> darling::Result<Self> {
+    Ok(EnvArg::Key(value.to_string()))
+  }
+
+  fn from_bool(value: bool) -> darling::Result<Self> {
+    Ok(EnvArg::Enabled(value))
+  }
+}
+
+/// `deprecated` marks a field as deprecated and `deprecated = "note"` adds a note to the warning
+#[derive(Debug, Clone)]
+pub struct DeprecatedArg(pub String);
+
+impl FromMeta for DeprecatedArg {
+  fn from_word() -> darling::Result<Self> {
+    Ok(DeprecatedArg(String::new()))
+  }
+
+  fn from_string(value: &str) -> darling::Result<Self> {
+    Ok(DeprecatedArg(value.to_string()))
+  }
+}
+
 #[derive(FromAttributes, Default)]
 #[darling(default, attributes(config))]
 pub struct FieldArgs {
   pub nested: bool,
   pub flatten: bool,
+
+  #[darling(rename = "env")]
+  pub env_arg: Option<SpannedValue<EnvArg>>,
+
+  /// The env key, filled from `env_arg` in [`FieldArgs::parse`]
+  #[darling(skip)]
   pub env: Option<SpannedValue<String>>,
 
   #[darling(with = preserve_str_literal, map = Some)]
@@ -71,6 +149,47 @@ pub struct FieldArgs {
   pub parse_env: Option<ExprPath>,
   pub merge: Option<ExprPath>,
   pub rename: Option<String>,
+
+  #[darling(default)]
+  pub skip: SpannedValue<bool>,
+
+  #[darling(default)]
+  pub flatten_extra: SpannedValue<bool>,
+
+  #[darling(default)]
+  pub env_seq: SpannedValue<bool>,
+
+  #[darling(default)]
+  pub coerce_strings: SpannedValue<bool>,
+
+  pub trim_env: Option<bool>,
+
+  #[darling(default)]
+  pub env_bool_presence: SpannedValue<bool>,
+
+  pub default_nested: Option<bool>,
+
+  pub requires: Option<SpannedValue<String>>,
+
+  pub conflicts_with: Option<SpannedValue<String>>,
+
+  pub deprecated: Option<DeprecatedArg>,
+}
+
+impl FieldArgs {
+  pub fn parse(attrs: &[syn::Attribute]) -> Result<Self, darling::Error> {
+    let mut args = Self::from_attributes(attrs)?;
+    if let Some(env_arg) = args.env_arg.take() {
+      let span = env_arg.span();
+      match env_arg.as_ref() {
+        EnvArg::Key(key) => args.env = Some(SpannedValue::new(key.clone(), span)),
+        EnvArg::Enabled(true) => {}
+        EnvArg::Enabled(false) => args.skip_env = SpannedValue::new(true, span),
+      }
+    }
+
+    Ok(args)
+  }
 }
 
 #[derive(FromAttributes, Default)]
@@ -83,24 +202,40 @@ pub struct ContainerAttrs {
   #[darling(default)]
   pub skip_env: SpannedValue<bool>,
   pub rename_all: Option<SpannedValue<String>>,
+  pub rename_all_env: Option<SpannedValue<String>>,
   pub allow_unknown_fields: bool,
+  pub env_strict: SpannedValue<bool>,
+  pub trim_env: bool,
+  pub default_nested: bool,
+  pub partial_derive: PathList,
 }
 
 impl ContainerAttrs {
   pub fn rename_all_inflection(&self) -> Result<Option<Inflection>, syn::Error> {
-    use std::str::FromStr;
-    match &self.rename_all {
-      None => Ok(None),
-      Some(v) => {
-        let span = v.span();
-        let value: &str = v;
-        let inflection = match Inflection::from_str(value) {
-          Ok(inflection) => inflection,
-          Err(()) => return Err(syn::Error::new(span, format!("unknown rename_all attribute value {}, valid alternatives are lowercase, UPPERCASE, snake_case, camelCase, PascalCase, kebab-case, SCREAMING_SNAKE_CASE and SCREAMING-KEBAB-CASE", value)))
-        };
-
-        Ok(Some(inflection))
-      }
+    parse_inflection("rename_all", self.rename_all.as_ref())
+  }
+
+  pub fn rename_all_env_inflection(&self) -> Result<Option<Inflection>, syn::Error> {
+    parse_inflection("rename_all_env", self.rename_all_env.as_ref())
+  }
+}
+
+fn parse_inflection(
+  attr: &str,
+  value: Option<&SpannedValue<String>>,
+) -> Result<Option<Inflection>, syn::Error> {
+  use std::str::FromStr;
+  match value {
+    None => Ok(None),
+    Some(v) => {
+      let span = v.span();
+      let value: &str = v;
+      let inflection = match Inflection::from_str(value) {
+        Ok(inflection) => inflection,
+        Err(()) => return Err(syn::Error::new(span, format!("unknown {} attribute value {}, valid alternatives are lowercase, UPPERCASE, snake_case, camelCase, PascalCase, kebab-case, SCREAMING_SNAKE_CASE and SCREAMING-KEBAB-CASE", attr, value)))
+      };
+
+      Ok(Some(inflection))
     }
   }
 }
@@ -113,3 +248,21 @@ pub fn preserve_str_literal(meta: &Meta) -> darling::Result<Expr> {
     Meta::NameValue(nv) => Ok(nv.value.clone()),
   }
 }
+
+/// Read the `///` doc comments of an item, each line is trimmed and the lines are joined with `\n`
+pub fn doc_comment(attrs: &[syn::Attribute]) -> String {
+  let mut lines = vec![];
+  for attr in attrs {
+    if !attr.path().is_ident("doc") {
+      continue;
+    }
+
+    if let Meta::NameValue(nv) = &attr.meta {
+      if let Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) = &nv.value {
+        lines.push(lit.value().trim().to_string());
+      }
+    }
+  }
+
+  lines.join("\n").trim().to_string()
+}
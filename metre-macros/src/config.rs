@@ -1,7 +1,9 @@
+use darling::util::SpannedValue;
 use darling::FromAttributes;
 use inflector::Inflector;
-use proc_macro2::{Ident, Span, TokenStream};
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, quote_spanned};
+use syn::ext::IdentExt;
 use syn::{DeriveInput, LitStr};
 
 use crate::attrs::*;
@@ -16,8 +18,25 @@ macro_rules! syn_err {
   };
 }
 
-fn fmt_has_prefix(fmt: &str) -> bool {
-  fmt.contains("{}")
+// env formats can have any number of `{}` placeholders that are filled with the prefix
+// and `{{` or `}}` for literal braces, see metre::util::fill_env_fmt
+fn validate_env_fmt(fmt: &LitStr) -> Result<(), syn::Error> {
+  let value = fmt.value();
+  let mut chars = value.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      ('{', Some('}')) | ('{', Some('{')) | ('}', Some('}')) => {
+        chars.next();
+      }
+      ('{', _) | ('}', _) => syn_err!(
+        fmt.span(),
+        "invalid env format, use `{}` for the prefix placeholder and `{{` or `}}` for literal braces"
+      ),
+      _ => {}
+    }
+  }
+
+  Ok(())
 }
 
 // this is a somehow hacky way to find if a type is Option
@@ -55,8 +74,12 @@ fn ty_is_option(ty: &syn::Type) -> bool {
 }
 
 pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
-  //let generics = &input.generics;
   let generics = &input.generics;
+  let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+  let has_type_params = generics.type_params().next().is_some();
+  let where_predicates = where_clause
+    .map(|where_clause| where_clause.predicates.iter().collect::<Vec<_>>())
+    .unwrap_or_default();
   let name = &input.ident;
   let vis = &input.vis;
   let container_attrs = ContainerAttrs::from_attributes(&input.attrs)?;
@@ -73,18 +96,13 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
     .unwrap_or_else(|| syn::Ident::new(&format!("Partial{}", name), Span::call_site()));
 
   let rename_all = container_attrs.rename_all_inflection()?;
+  let rename_all_env = container_attrs.rename_all_env_inflection()?;
   let rename_all_serde_attr = rename_all.map(|_| {
     let spanned = container_attrs.rename_all.as_ref().unwrap();
     let lit = LitStr::new(spanned, spanned.span());
     quote! { #[serde(rename_all = #lit)] }
   });
 
-  let deny_unknown_attr = if container_attrs.allow_unknown_fields {
-    quote! {}
-  } else {
-    quote! { #[serde(deny_unknown_fields)] }
-  };
-
   if *container_attrs.skip_env {
     if let Some(env_prefix) = container_attrs.env_prefix {
       syn_err!(
@@ -94,15 +112,22 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
     }
   };
 
+  if *container_attrs.skip_env && *container_attrs.env_strict {
+    syn_err!(
+      container_attrs.env_strict.span(),
+      "cannot use both env_strict and skip_env in the same item"
+    )
+  };
+
   let container_env_prefix_fmt: LitStr = container_attrs
     .env_prefix
     .map(|v| LitStr::new(&v, v.span()))
     .unwrap_or_else(|| LitStr::new("{}", Span::call_site()));
 
-  let get_container_env_prefix = if fmt_has_prefix(&container_env_prefix_fmt.value()) {
-    quote! { format!(#container_env_prefix_fmt, env_prefix) }
-  } else {
-    quote! { String::from(#container_env_prefix_fmt) }
+  validate_env_fmt(&container_env_prefix_fmt)?;
+
+  let get_container_env_prefix = quote! {
+    #metre::util::fill_env_fmt(#container_env_prefix_fmt, env_prefix)
   };
 
   let item = match &input.data {
@@ -121,10 +146,27 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
   let mut destructure_fields = Vec::<TokenStream>::new();
   let mut merge_partial_fields = Vec::<TokenStream>::new();
   let mut from_env_fields = Vec::<TokenStream>::new();
+  let mut env_keys_stmts = Vec::<TokenStream>::new();
+  let mut property_env_key_stmts = Vec::<TokenStream>::new();
+  let mut env_vars_stmts = Vec::<TokenStream>::new();
+  let mut field_docs_stmts = Vec::<TokenStream>::new();
+  let mut deprecated_properties_stmts = Vec::<TokenStream>::new();
   let mut missing_fields_stmts = Vec::<TokenStream>::new();
   let mut is_empty_stmts = Vec::<TokenStream>::new();
+  let mut present_fields_stmts = Vec::<TokenStream>::new();
   let mut from_partial_fields = Vec::<TokenStream>::new();
   let mut default_fields = Vec::<TokenStream>::new();
+  let mut has_flatten_extra = false;
+  // used to generate the bounds of the partial struct impls for generic configs
+  let mut partial_field_idents = Vec::<syn::Ident>::new();
+  let mut partial_field_types = Vec::<TokenStream>::new();
+  // ident, nested flag and deep path of each partial field by field name, used for cross-field checks
+  let mut field_refs = std::collections::HashMap::<String, (syn::Ident, bool, String)>::new();
+  let mut requires = Vec::<(String, SpannedValue<String>)>::new();
+  let mut conflicts_with = Vec::<(String, SpannedValue<String>)>::new();
+  let mut conflicts_stmts = Vec::<TokenStream>::new();
+  // env key formats of the fields that read a single env key, to detect duplicated keys
+  let mut env_fmts = std::collections::HashMap::<String, String>::new();
 
   for field in &fields.named {
     let vis = &field.vis;
@@ -139,14 +181,117 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
     }
 
-    let attrs = FieldArgs::from_attributes(&field.attrs)?;
+    let attrs = FieldArgs::parse(&field.attrs)?;
 
-    let field_name = match &attrs.rename {
-      Some(name) => Ident::new(name, span),
-      None => match rename_all {
-        Some(inflection) => Ident::new(&inflection.apply(&ident.to_string()), ident.span()),
-        None => ident.clone(),
-      },
+    if *attrs.skip {
+      if attrs.nested || attrs.flatten || attrs.env.is_some() || attrs.parse_env.is_some() || attrs.merge.is_some() || attrs.rename.is_some() || *attrs.skip_env || *attrs.env_bool_presence || attrs.requires.is_some() || attrs.conflicts_with.is_some() || attrs.deprecated.is_some() {
+        syn_err!(
+          attrs.skip.span(),
+          "skip can only be combined with the default attribute"
+        );
+      }
+
+      // skipped fields are not part of the partial struct
+      // and are initialized with the default attribute or with Default::default()
+      let default_expr = match &attrs.default {
+        Some(expr) => quote! { #expr },
+        None => span_quote! { ::core::default::Default::default() },
+      };
+
+      from_partial_fields.push(span_quote! {
+        #ident: #default_expr,
+      });
+
+      continue;
+    }
+
+    if *attrs.env_seq {
+      if !attrs.nested {
+        syn_err!(
+          attrs.env_seq.span(),
+          "env_seq can only be used in nested list fields"
+        );
+      }
+
+      if *container_attrs.env_strict {
+        syn_err!(
+          attrs.env_seq.span(),
+          "env_seq cannot be used in an item with env_strict"
+        );
+      }
+    }
+
+    if *attrs.flatten_extra {
+      if attrs.nested || attrs.flatten || attrs.env.is_some() || attrs.parse_env.is_some() || attrs.merge.is_some() || attrs.rename.is_some() || attrs.default.is_some() || *attrs.skip_env || *attrs.env_bool_presence || attrs.requires.is_some() || attrs.conflicts_with.is_some() || attrs.deprecated.is_some() {
+        syn_err!(
+          attrs.flatten_extra.span(),
+          "flatten_extra cannot be combined with other attributes"
+        );
+      }
+
+      if has_flatten_extra {
+        syn_err!(
+          attrs.flatten_extra.span(),
+          "only one field can have the flatten_extra attribute"
+        );
+      }
+
+      has_flatten_extra = true;
+
+      // the catch-all map is stored as is in the partial struct
+      // and collects all the unknown keys of the serialized representation
+      partial_fields_declaration.push(span_quote! {
+        #[serde(flatten)]
+        #vis #ident: #ty,
+      });
+
+      partial_field_idents.push(ident.clone());
+      partial_field_types.push(quote! { #ty });
+
+      destructure_fields.push(span_quote! {#ident,});
+
+      merge_partial_fields.push(span_quote! {
+        ::core::iter::Extend::extend(&mut self.#ident, #ident);
+      });
+
+      from_env_fields.push(span_quote! {
+        #ident: ::core::default::Default::default(),
+      });
+
+      default_fields.push(span_quote! {
+        #ident: ::core::default::Default::default(),
+      });
+
+      is_empty_stmts.push(span_quote! {
+        if !#metre::merge::IsEmpty::is_empty(&self.#ident) {
+          return false;
+        };
+      });
+
+      present_fields_stmts.push(span_quote! {
+        for key in self.#ident.keys() {
+          present_fields.push(::std::string::ToString::to_string(key));
+        };
+      });
+
+      from_partial_fields.push(span_quote! {
+        #ident,
+      });
+
+      continue;
+    }
+
+    // the serialized name of the field, it is a String and not an Ident
+    // because renamed fields can have any name like "my-field"
+    let field_name: String = match &attrs.rename {
+      Some(name) => name.clone(),
+      None => {
+        let name = ident.unraw().to_string();
+        match rename_all {
+          Some(inflection) => inflection.apply_to_field(&name),
+          None => name,
+        }
+      }
     };
 
     let serde_partial_rename_attr = match &attrs.rename {
@@ -159,9 +304,14 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       true => quote! { #[serde(flatten)] },
     };
 
-    let env_name = match &attrs.rename {
-      Some(name) => name.to_string().to_screaming_snake_case(),
-      None => ident.to_string().to_screaming_snake_case(),
+    // without rename_all_env the env key is derived from the same name used for serde, after applying rename and rename_all,
+    // with rename_all_env it is derived from the explicit rename or the field ident, ignoring rename_all
+    let env_name = match rename_all_env {
+      Some(inflection) => match &attrs.rename {
+        Some(name) => inflection.apply(name),
+        None => inflection.apply(&ident.unraw().to_string()),
+      },
+      None => field_name.to_screaming_snake_case(),
     };
 
     let env_fmt: LitStr = attrs
@@ -176,15 +326,35 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
         }
       });
 
-    let get_field_env_key = if fmt_has_prefix(&env_fmt.value()) {
-      span_quote! { format!(#env_fmt, container_env_prefix) }
-    } else {
-      span_quote! { #env_fmt.to_string() }
+    validate_env_fmt(&env_fmt)?;
+
+    let get_field_env_key = span_quote! {
+      #metre::util::fill_env_fmt(#env_fmt, &container_env_prefix)
     };
 
+    let doc = doc_comment(&field.attrs);
+
+    // the property must be present in some stage to finish the config
+    let required = !is_option && attrs.default.is_none();
+
+    if attrs.default_nested == Some(true) && !(attrs.nested && is_option) {
+      syn_err!(
+        span,
+        "default_nested can only be used in optional nested fields"
+      );
+    }
+
+    // optional nested fields that are materialized with their defaults even if they are empty
+    let default_nested = attrs.nested && is_option && attrs.default_nested.unwrap_or(container_attrs.default_nested);
+
     match attrs.default {
       None => {
-        if attrs.nested {
+        if default_nested {
+          // materialize the optional nested block with its defaults even if they are empty
+          default_fields.push(quote! {
+            #ident: ::core::option::Option::Some(<<#ty as #metre::util::UnOption>::T as #metre::Config>::Partial::defaults()),
+          })
+        } else if attrs.nested {
           default_fields.push(quote! {
             #ident: <#ty as #metre::Config>::Partial::defaults(),
           })
@@ -196,9 +366,17 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
 
       Some(expr) => {
-        default_fields.push(quote! {
-          #ident: ::core::option::Option::Some(#expr),
-        });
+        // the partial slot of optional fields is Option<Option<T>>
+        // and the default value is of the inner type T
+        if is_option {
+          default_fields.push(quote! {
+            #ident: ::core::option::Option::Some(::core::option::Option::Some(#expr)),
+          });
+        } else {
+          default_fields.push(quote! {
+            #ident: ::core::option::Option::Some(#expr),
+          });
+        }
       }
     };
 
@@ -206,7 +384,15 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
     let mut merge_fn: TokenStream;
     let mut merge_map_err: TokenStream;
 
-    let field_name_str = field_name.to_string();
+    let field_name_str = field_name.clone();
+
+    // flattened nested fields are at the top level of the serialized representation
+    // so their deep properties are reported without the field name
+    let field_path_str = if attrs.flatten {
+      String::new()
+    } else {
+      field_name_str.clone()
+    };
 
     match attrs.nested {
       false => {
@@ -219,11 +405,9 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
         partial_ty = span_quote! { <#ty as #metre::Config>::Partial };
         merge_fn = span_quote! { #metre::util::merge_nested };
         merge_map_err = quote! {
-          .map_err(|e| {
-            #metre::error::MergeError {
-              field: format!("{}.{}", #field_name_str, e.field),
-              message: e.message
-            }
+          .map_err(|mut e| {
+            e.field = #metre::util::join_path(#field_path_str, &e.field);
+            e
           })
         };
       }
@@ -233,14 +417,57 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       merge_fn = quote! { #merge };
       merge_map_err = span_quote! {
         .map_err(|e| {
-          #metre::error::MergeError {
-            field: String::from(#field_name_str),
-            message: e.to_string()
-          }
+          // errors that implement std::error::Error are kept as the source of the merge error
+          #[allow(unused_imports)]
+          use #metre::util::{ErrorSourceFromDisplay as _, ErrorSourceFromError as _};
+          #metre::util::custom_merge_error(#field_name_str, #metre::util::ErrorSource(e).into_message_and_source())
         })
       }
     }
 
+    if !doc.is_empty() && !field_path_str.is_empty() {
+      field_docs_stmts.push(span_quote! {
+        docs.push((String::from(#field_path_str), String::from(#doc)));
+      });
+    }
+
+    if attrs.nested {
+      field_docs_stmts.push(span_quote! {
+        for (path, doc) in <#partial_ty as #metre::PartialConfig>::field_docs() {
+          docs.push((#metre::util::join_path(#field_path_str, &path), doc));
+        }
+      });
+    }
+
+    if let Some(deprecated) = &attrs.deprecated {
+      if attrs.flatten {
+        syn_err!(
+          ident.span(),
+          "deprecated cannot be used in flatten fields, mark the fields of the flattened struct instead"
+        );
+      }
+
+      let note = &deprecated.0;
+      let is_set = match attrs.nested {
+        true => span_quote! { !#metre::PartialConfig::list_present_properties(&self.#ident).is_empty() },
+        false => span_quote! { self.#ident.is_some() },
+      };
+
+      deprecated_properties_stmts.push(span_quote! {
+        if #is_set {
+          deprecated.push((String::from(#field_path_str), String::from(#note)));
+        }
+      });
+    }
+
+    if attrs.nested {
+      deprecated_properties_stmts.push(span_quote! {
+        for (prop, note) in #metre::PartialConfig::list_deprecated_properties(&self.#ident) {
+          deprecated.push((#metre::util::join_path(#field_path_str, &prop), note));
+        }
+      });
+    }
+
     if *attrs.skip_env {
       if let Some(env) = attrs.env {
         syn_err!(
@@ -250,7 +477,29 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
     };
 
+    if *attrs.env_bool_presence && (attrs.nested || attrs.flatten || attrs.parse_env.is_some() || *attrs.skip_env) {
+      syn_err!(
+        attrs.env_bool_presence.span(),
+        "env_bool_presence cannot be used with nested, flatten, parse_env or skip_env"
+      );
+    }
+
     let parse_env_fn = match &attrs.parse_env {
+      // the field is true if the env key is present, whatever its value is
+      None if *attrs.env_bool_presence => {
+        let value = if is_option {
+          span_quote! { ::core::option::Option::Some(::core::option::Option::Some(true)) }
+        } else {
+          span_quote! { ::core::option::Option::Some(true) }
+        };
+
+        span_quote! {
+          {
+            let _ = &env_value;
+            ::core::result::Result::<_, ::std::convert::Infallible>::Ok(#value)
+          }
+        }
+      }
       None => {
         if is_option {
           span_quote! { <<#ty as #metre::util::UnOption>::T as ::std::str::FromStr>::from_str(&env_value).map(|v| ::core::option::Option::Some(::core::option::Option::Some(v))) }
@@ -267,6 +516,25 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
     };
 
+    let serde_deserialize_with_attr = if *attrs.coerce_strings {
+      if attrs.nested || attrs.flatten {
+        syn_err!(
+          attrs.coerce_strings.span(),
+          "coerce_strings cannot be used in nested or flatten fields"
+        );
+      }
+
+      let path = if is_option {
+        format!("{}::util::coerce_string_option", metre)
+      } else {
+        format!("{}::util::coerce_string", metre)
+      };
+
+      span_quote! { #[serde(deserialize_with = #path)] }
+    } else {
+      quote! {}
+    };
+
     let serde_skip_serializing_if = if attrs.nested {
       let path = format!("{}::PartialConfig::is_empty", metre);
       span_quote! { #[serde(skip_serializing_if = #path)] }
@@ -279,21 +547,46 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       #serde_skip_serializing_if
       #serde_partial_rename_attr
       #serde_flatten_attr
+      #serde_deserialize_with_attr
       #vis #ident: #partial_ty,
     });
 
+    partial_field_idents.push(ident.clone());
+    partial_field_types.push(partial_ty.clone());
+
     destructure_fields.push(span_quote! {#ident,});
 
     merge_partial_fields.push(span_quote! {
       #merge_fn(&mut self.#ident, #ident)#merge_map_err?;
     });
 
+    field_refs.insert(ident.unraw().to_string(), (ident.clone(), attrs.nested, field_name_str.clone()));
+
+    if let Some(other) = attrs.requires.clone() {
+      requires.push((ident.unraw().to_string(), other));
+    }
+
+    if let Some(other) = attrs.conflicts_with.clone() {
+      conflicts_with.push((ident.unraw().to_string(), other));
+    }
+
     if attrs.nested {
-      missing_fields_stmts.push(span_quote! {
-        for prop in #metre::PartialConfig::list_missing_properties(&self.#ident) {
-          missing_fields.push(format!("{}.{}", #field_name_str, prop));
-        };
-      });
+      if default_nested {
+        // a present block is checked even if it is empty, as it will not be converted to None
+        missing_fields_stmts.push(span_quote! {
+          if let ::core::option::Option::Some(inner) = &self.#ident {
+            for prop in #metre::PartialConfig::list_missing_properties(inner) {
+              missing_fields.push(#metre::util::join_path(#field_path_str, &prop));
+            };
+          };
+        });
+      } else {
+        missing_fields_stmts.push(span_quote! {
+          for prop in #metre::PartialConfig::list_missing_properties(&self.#ident) {
+            missing_fields.push(#metre::util::join_path(#field_path_str, &prop));
+          };
+        });
+      }
 
       is_empty_stmts.push(span_quote! {
         if !#metre::PartialConfig::is_empty(&self.#ident) {
@@ -301,9 +594,42 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
         };
       });
 
-      from_partial_fields.push(span_quote! {
-        #ident: #metre::Config::from_partial(#ident).unwrap(),
+      present_fields_stmts.push(span_quote! {
+        for prop in #metre::PartialConfig::list_present_properties(&self.#ident) {
+          present_fields.push(#metre::util::join_path(#field_path_str, &prop));
+        };
       });
+
+      let map_from_partial_err = span_quote! {
+        |e| {
+          // set the correct deep path to the missing properties
+          let missing_properties = e.missing_properties
+            .into_iter()
+            .map(|prop| #metre::util::join_path(#field_path_str, &prop))
+            .collect();
+          let conflicting_properties = e.conflicting_properties
+            .into_iter()
+            .map(|(a, b)| (#metre::util::join_path(#field_path_str, &a), #metre::util::join_path(#field_path_str, &b)))
+            .collect();
+          #metre::error::FromPartialError::new(::std::any::type_name::<Self>(), missing_properties)
+            .with_conflicting_properties(conflicting_properties)
+        }
+      };
+
+      if default_nested {
+        from_partial_fields.push(span_quote! {
+          #ident: match #ident {
+            ::core::option::Option::None => ::core::option::Option::None,
+            ::core::option::Option::Some(inner) => ::core::option::Option::Some(
+              <<#ty as #metre::util::UnOption>::T as #metre::Config>::from_partial(inner).map_err(#map_from_partial_err)?
+            ),
+          },
+        });
+      } else {
+        from_partial_fields.push(span_quote! {
+          #ident: #metre::Config::from_partial(#ident).map_err(#map_from_partial_err)?,
+        });
+      }
     } else {
       if !is_option {
         missing_fields_stmts.push(span_quote! {
@@ -319,6 +645,12 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
         };
       });
 
+      present_fields_stmts.push(span_quote! {
+        if ::core::option::Option::is_some(&self.#ident) {
+          present_fields.push(String::from(#field_name_str));
+        };
+      });
+
       if !is_option {
         from_partial_fields.push(span_quote! {
           #ident: ::core::option::Option::unwrap(#ident),
@@ -330,7 +662,7 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
     }
 
-    let field_name_lit = LitStr::new(&field_name.to_string(), field_name.span());
+    let field_name_lit = LitStr::new(&field_name, span);
 
     let skip_env = {
       if *container_attrs.skip_env {
@@ -340,10 +672,37 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
     };
 
+    if !skip_env && !attrs.nested && !attrs.flatten {
+      let fmt = env_fmt.value();
+      if let Some(other) = env_fmts.get(&fmt) {
+        syn_err!(
+          env_fmt.span(),
+          format!("duplicate env key {fmt:?}, it is also used by field {other}")
+        );
+      }
+
+      env_fmts.insert(fmt, ident.unraw().to_string());
+    }
+
     let from_env_field: TokenStream;
 
     if skip_env {
-      from_env_field = span_quote! { #ident: ::core::option::Option::None, }
+      from_env_field = span_quote! { #ident: ::core::default::Default::default(), }
+    } else if *attrs.env_seq {
+      from_env_field = span_quote! {
+        #ident: {
+
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          #metre::util::env_seq(env, &nested_prefix).map_err(|mut e| {
+            e.field = #metre::util::join_path(#field_path_str, &e.field);
+            e
+          })?
+        },
+      };
     } else if attrs.nested {
       from_env_field = span_quote! {
         #ident: {
@@ -353,13 +712,10 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
             nested_prefix.push('_');
           }
 
-          #metre::PartialConfig::from_env_with_provider_and_prefix(env, &nested_prefix).map_err(|e| {
+          #metre::PartialConfig::from_env_with_provider_and_prefix(env, &nested_prefix).map_err(|mut e| {
             // set the correct deep path to the field
-            #metre::error::FromEnvError {
-              key: e.key,
-              field: format!("{}.{}", #field_name_lit, e.field),
-              message: e.message,
-            }
+            e.field = #metre::util::join_path(#field_path_str, &e.field);
+            e
           })?
         },
       };
@@ -370,27 +726,32 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       //   quote! { value }
       // };
 
+      let trim_env_value = if attrs.trim_env.unwrap_or(container_attrs.trim_env) {
+        quote! { let env_value = env_value.trim().to_string(); }
+      } else {
+        quote! {}
+      };
+
       from_env_field = span_quote! {
         #ident: {
           let key = #get_field_env_key;
 
           let env_string_option = env.get(&key).map_err(|e| {
-            #metre::error::FromEnvError {
-              key: key.clone(),
-              field: String::from(#field_name_lit),
-              message: e.to_string(),
-            }
+            #metre::error::FromEnvError::new(key.clone(), #field_name_lit, e.to_string())
           })?;
 
          match env_string_option {
             None => ::core::option::Option::None,
             Some(env_value) => {
+              #trim_env_value
               #parse_env_fn.map_err(|e| {
-                #metre::error::FromEnvError {
-                  key,
-                  field: String::from(#field_name_lit),
-                  message: e.to_string(),
-                }
+                // errors that implement std::error::Error are kept as the source of the env error
+                #[allow(unused_imports)]
+                use #metre::util::{ErrorSourceFromDisplay as _, ErrorSourceFromError as _};
+                let (message, source) = #metre::util::ErrorSource(e).into_message_and_source();
+                let mut err = #metre::error::FromEnvError::new(key, #field_name_lit, message);
+                err.source = source;
+                err
               })?
             },
           }
@@ -399,19 +760,255 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
     }
 
     from_env_fields.push(from_env_field);
+
+    if skip_env {
+      // this field doesn't read any env key
+    } else if *attrs.env_seq {
+      env_keys_stmts.push(span_quote! {
+        {
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          keys.push(format!("{}COUNT", nested_prefix));
+        }
+      });
+    } else if attrs.nested {
+      env_keys_stmts.push(span_quote! {
+        {
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          keys.extend(<#partial_ty as #metre::PartialConfig>::env_keys_with_optional_prefix(Some(&nested_prefix)));
+        }
+      });
+    } else {
+      env_keys_stmts.push(span_quote! {
+        keys.push(#get_field_env_key);
+      });
+    }
+
+    if skip_env {
+      // this field doesn't read any env key
+    } else if *attrs.env_seq {
+      env_vars_stmts.push(span_quote! {
+        {
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          vars.push(#metre::EnvVar {
+            key: format!("{}COUNT", nested_prefix),
+            field: String::from(#field_path_str),
+            required: false,
+            doc: String::from(#doc),
+          });
+        }
+      });
+    } else if attrs.nested {
+      env_vars_stmts.push(span_quote! {
+        {
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          for var in <#partial_ty as #metre::PartialConfig>::env_vars_with_optional_prefix(Some(&nested_prefix)) {
+            vars.push(#metre::EnvVar {
+              field: #metre::util::join_path(#field_path_str, &var.field),
+              ..var
+            });
+          }
+        }
+      });
+    } else {
+      env_vars_stmts.push(span_quote! {
+        vars.push(#metre::EnvVar {
+          key: #get_field_env_key,
+          field: String::from(#field_name_str),
+          required: #required,
+          doc: String::from(#doc),
+        });
+      });
+    }
+
+    if skip_env || *attrs.env_seq {
+      // the env key of this field properties is unknown
+    } else if attrs.nested {
+      property_env_key_stmts.push(span_quote! {
+        if let Some(rest) = #metre::util::strip_path(property, #field_path_str) {
+          let mut nested_prefix: String = #get_field_env_key;
+          if !nested_prefix.is_empty() && !nested_prefix.ends_with('_') {
+            nested_prefix.push('_');
+          }
+
+          if let Some(key) = <#partial_ty as #metre::PartialConfig>::property_env_key(rest, Some(&nested_prefix)) {
+            return Some(key);
+          }
+        }
+      });
+    } else {
+      property_env_key_stmts.push(span_quote! {
+        if property == #field_name_str {
+          return Some(#get_field_env_key);
+        }
+      });
+    }
   }
 
-  let partial_struct_declaration = quote! {
-    #[derive(::std::fmt::Debug, ::std::default::Default, ::serde::Serialize, ::serde::Deserialize)]
-    #rename_all_serde_attr
-    #deny_unknown_attr
-    #vis struct #partial_name #generics {
-      #(#partial_fields_declaration)*
+  let presence_check = |receiver: TokenStream, ident: &syn::Ident, nested: bool| {
+    if nested {
+      quote! { !#metre::PartialConfig::is_empty(&#receiver.#ident) }
+    } else {
+      quote! { ::core::option::Option::is_some(&#receiver.#ident) }
+    }
+  };
+
+  for (field, other) in requires {
+    let (ident, nested, _) = &field_refs[&field];
+    let (other_ident, other_nested, other_path) = match field_refs.get(other.as_str()) {
+      Some(other_ref) => other_ref,
+      None => syn_err!(
+        other.span(),
+        format!("unknown field {} in requires attribute", other.as_str())
+      ),
+    };
+
+    let is_present = presence_check(quote! { self }, ident, *nested);
+    let other_is_present = presence_check(quote! { self }, other_ident, *other_nested);
+
+    // the required field is reported as missing if this field is present
+    missing_fields_stmts.push(quote! {
+      if #is_present && !(#other_is_present) {
+        let prop = String::from(#other_path);
+        if !missing_fields.contains(&prop) {
+          missing_fields.push(prop);
+        }
+      };
+    });
+  }
+
+  for (field, other) in conflicts_with {
+    let (ident, nested, path) = &field_refs[&field];
+    let (other_ident, other_nested, other_path) = match field_refs.get(other.as_str()) {
+      Some(other_ref) => other_ref,
+      None => syn_err!(
+        other.span(),
+        format!("unknown field {} in conflicts_with attribute", other.as_str())
+      ),
+    };
+
+    let is_present = presence_check(quote! { partial }, ident, *nested);
+    let other_is_present = presence_check(quote! { partial }, other_ident, *other_nested);
+
+    conflicts_stmts.push(quote! {
+      if #is_present && #other_is_present {
+        conflicting_properties.push((String::from(#path), String::from(#other_path)));
+      };
+    });
+  }
+
+  // serde doesn't support deny_unknown_fields in combination with a flattened catch-all map
+  let deny_unknown_attr = if container_attrs.allow_unknown_fields || has_flatten_extra {
+    quote! {}
+  } else {
+    quote! { #[serde(deny_unknown_fields)] }
+  };
+
+  let env_strict_check = if *container_attrs.env_strict {
+    quote! {
+      if !container_env_prefix.is_empty() {
+        if let Some(keys) = #metre::EnvProvider::keys(env) {
+          let known_keys = {
+            let mut keys = vec![];
+            #(#env_keys_stmts)*
+            keys
+          };
+          for key in keys {
+            if key.starts_with(&container_env_prefix) && !known_keys.contains(&key) {
+              return Err(#metre::error::FromEnvError::new(
+                key,
+                "",
+                "unknown env variable, no field is loaded from this key",
+              ));
+            }
+          }
+        }
+      }
+    }
+  } else {
+    quote! {}
+  };
+
+  let partial_derive = container_attrs.partial_derive.iter();
+
+  // for generic configs the derived bounds (eg: `T: Default`) would be required for the config type params,
+  // so the bounds are set on the partial field types instead
+  let partial_struct_declaration = if has_type_params {
+    let serialize_bound = partial_field_types
+      .iter()
+      .map(|ty| format!("{}: ::serde::Serialize", ty))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let deserialize_bound = partial_field_types
+      .iter()
+      .map(|ty| format!("{}: ::serde::Deserialize<'de>", ty))
+      .collect::<Vec<_>>()
+      .join(", ");
+
+    let partial_name_str = partial_name.to_string();
+
+    quote! {
+      #[derive(::serde::Serialize, ::serde::Deserialize #(, #partial_derive)*)]
+      #[serde(bound(serialize = #serialize_bound, deserialize = #deserialize_bound))]
+      #rename_all_serde_attr
+      #deny_unknown_attr
+      #vis struct #partial_name #impl_generics #where_clause {
+        #(#partial_fields_declaration)*
+      }
+
+      impl #impl_generics ::std::default::Default for #partial_name #ty_generics
+      where
+        #(#where_predicates,)*
+        #(#partial_field_types: ::std::default::Default,)*
+      {
+        fn default() -> Self {
+          Self {
+            #(#partial_field_idents: ::std::default::Default::default(),)*
+          }
+        }
+      }
+
+      impl #impl_generics ::std::fmt::Debug for #partial_name #ty_generics
+      where
+        #(#where_predicates,)*
+        #(#partial_field_types: ::std::fmt::Debug,)*
+      {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+          f.debug_struct(#partial_name_str)
+            #(.field(::core::stringify!(#partial_field_idents), &self.#partial_field_idents))*
+            .finish()
+        }
+      }
+    }
+  } else {
+    quote! {
+      #[derive(::std::fmt::Debug, ::std::default::Default, ::serde::Serialize, ::serde::Deserialize #(, #partial_derive)*)]
+      #rename_all_serde_attr
+      #deny_unknown_attr
+      #vis struct #partial_name #impl_generics #where_clause {
+        #(#partial_fields_declaration)*
+      }
     }
   };
 
   let partial_impl = quote! {
-    impl #generics #metre::PartialConfig for #partial_name #generics {
+    impl #impl_generics #metre::PartialConfig for #partial_name #ty_generics #where_clause {
 
       fn defaults() -> Self {
         Self {
@@ -430,13 +1027,50 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
       }
 
       fn from_env_with_provider_and_optional_prefix<E: #metre::EnvProvider>(env: &E, prefix: Option<&str>) -> Result<Self, #metre::error::FromEnvError> {
+        <Self as #metre::PartialConfig>::from_env_with_provider_and_env_prefix_fmt(env, prefix, None)
+      }
+
+      fn from_env_with_provider_and_env_prefix_fmt<E: #metre::EnvProvider>(env: &E, prefix: Option<&str>, env_prefix_fmt: Option<&str>) -> Result<Self, #metre::error::FromEnvError> {
 
         let env_prefix = prefix.unwrap_or("");
-        let container_env_prefix = #get_container_env_prefix;
+        let container_env_prefix = match env_prefix_fmt {
+          Some(env_prefix_fmt) => #metre::util::fill_env_fmt(env_prefix_fmt, env_prefix),
+          None => #get_container_env_prefix,
+        };
 
-        Ok(Self {
+        let partial = Self {
           #(#from_env_fields)*
-        })
+        };
+
+        #env_strict_check
+
+        Ok(partial)
+      }
+
+      fn env_keys_with_optional_prefix(prefix: Option<&str>) -> Vec<String> {
+        let env_prefix = prefix.unwrap_or("");
+        let container_env_prefix = #get_container_env_prefix;
+
+        let mut keys = vec![];
+        #(#env_keys_stmts)*
+        keys
+      }
+
+      fn env_vars_with_optional_prefix(prefix: Option<&str>) -> Vec<#metre::EnvVar> {
+        let env_prefix = prefix.unwrap_or("");
+        let container_env_prefix = #get_container_env_prefix;
+
+        let mut vars = vec![];
+        #(#env_vars_stmts)*
+        vars
+      }
+
+      fn property_env_key(property: &str, prefix: Option<&str>) -> Option<String> {
+        let env_prefix = prefix.unwrap_or("");
+        let container_env_prefix = #get_container_env_prefix;
+
+        #(#property_env_key_stmts)*
+        None
       }
 
       fn list_missing_properties(&self) -> Vec<String> {
@@ -449,19 +1083,44 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
         #(#is_empty_stmts)*
         true
       }
+
+      fn list_present_properties(&self) -> Vec<String> {
+        let mut present_fields = vec![];
+        #(#present_fields_stmts)*
+        present_fields
+      }
+
+      fn field_docs() -> Vec<(String, String)> {
+        let mut docs = vec![];
+        #(#field_docs_stmts)*
+        docs
+      }
+
+      fn list_deprecated_properties(&self) -> Vec<(String, String)> {
+        #[allow(unused_mut)]
+        let mut deprecated = vec![];
+        #(#deprecated_properties_stmts)*
+        deprecated
+      }
     }
   };
 
   let config_impl = quote! {
-    impl #generics #metre::Config for #name #generics {
-      type Partial = #partial_name #generics;
+    impl #impl_generics #metre::Config for #name #ty_generics #where_clause {
+      type Partial = #partial_name #ty_generics;
       fn from_partial(partial: Self::Partial) -> Result<Self, #metre::error::FromPartialError> {
 
         let missing_properties = #metre::PartialConfig::list_missing_properties(&partial);
-        if !missing_properties.is_empty() {
-          return Err(#metre::error::FromPartialError {
-            missing_properties
-          });
+
+        #[allow(unused_mut)]
+        let mut conflicting_properties: Vec<(String, String)> = vec![];
+        #(#conflicts_stmts)*
+
+        if !missing_properties.is_empty() || !conflicting_properties.is_empty() {
+          return Err(
+            #metre::error::FromPartialError::new(::std::any::type_name::<Self>(), missing_properties)
+              .with_conflicting_properties(conflicting_properties),
+          );
         }
 
         let Self::Partial {
@@ -482,11 +1141,19 @@ pub fn config(input: DeriveInput) -> Result<TokenStream, syn::Error> {
 
     #partial_impl
 
-    impl #generics TryFrom<#partial_name #generics> for #name #generics {
+    impl #impl_generics #partial_name #ty_generics #where_clause {
+      /// Create the final configuration from this partial configuration, same as `Config::from_partial`
+      #[inline(always)]
+      pub fn into_config(self) -> Result<#name #ty_generics, #metre::error::FromPartialError> {
+        <#name #ty_generics as #metre::Config>::from_partial(self)
+      }
+    }
+
+    impl #impl_generics TryFrom<#partial_name #ty_generics> for #name #ty_generics #where_clause {
       type Error = #metre::error::FromPartialError;
       #[inline(always)]
-      fn try_from(partial: #partial_name #generics) -> Result<Self, Self::Error> {
-          <#name #generics as #metre::Config>::from_partial(partial)
+      fn try_from(partial: #partial_name #ty_generics) -> Result<Self, Self::Error> {
+          <#name #ty_generics as #metre::Config>::from_partial(partial)
       }
     }
   };
@@ -0,0 +1,14 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Inner {
+  port: u16,
+}
+
+#[derive(Config)]
+struct Conf {
+  #[config(nested, default_nested)]
+  inner: Inner,
+}
+
+fn main() {}
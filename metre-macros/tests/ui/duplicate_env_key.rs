@@ -0,0 +1,10 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  port: u16,
+  #[config(env = "{}PORT")]
+  other_port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use std::collections::HashMap;
+
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(flatten_extra)]
+  extra: HashMap<String, String>,
+  #[config(flatten_extra)]
+  more: HashMap<String, String>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+enum Conf {
+  A,
+  B,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use metre::Config;
+
+fn parse_flag(value: &str) -> Result<Option<bool>, String> {
+  Ok(Some(value == "1"))
+}
+
+#[derive(Config)]
+struct Conf {
+  #[config(env_bool_presence, parse_env = parse_flag)]
+  verbose: bool,
+}
+
+fn main() {}
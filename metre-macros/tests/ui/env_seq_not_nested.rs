@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(env_seq)]
+  ports: Vec<u16>,
+}
+
+fn main() {}
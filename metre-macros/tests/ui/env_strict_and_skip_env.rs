@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+#[config(env_strict, skip_env)]
+struct Conf {
+  port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use std::collections::HashMap;
+
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(flatten_extra, default = HashMap::new())]
+  extra: HashMap<String, String>,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(env = "{PORT")]
+  port: u16,
+}
+
+fn main() {}
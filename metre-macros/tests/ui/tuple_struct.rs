@@ -0,0 +1,6 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf(u16);
+
+fn main() {}
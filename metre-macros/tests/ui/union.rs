@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+union Conf {
+  a: u16,
+  b: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(conflicts_with = "socket")]
+  port: Option<u16>,
+}
+
+fn main() {}
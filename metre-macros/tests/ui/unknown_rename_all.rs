@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+#[config(rename_all = "sPoNgEcAsE")]
+struct Conf {
+  port: u16,
+}
+
+fn main() {}
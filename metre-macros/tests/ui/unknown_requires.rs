@@ -0,0 +1,9 @@
+use metre::Config;
+
+#[derive(Config)]
+struct Conf {
+  #[config(requires = "password")]
+  user: Option<String>,
+}
+
+fn main() {}
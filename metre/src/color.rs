@@ -0,0 +1,21 @@
+//! Terminal colors for error messages and load locations
+//!
+//! With the `color` feature disabled the [`Colorize`] methods return the value unchanged
+//! and the `owo-colors` dependency is not compiled
+
+#[cfg(feature = "color")]
+pub(crate) use owo_colors::OwoColorize as Colorize;
+
+#[cfg(not(feature = "color"))]
+pub(crate) trait Colorize {
+  fn yellow(&self) -> &Self {
+    self
+  }
+
+  fn dimmed(&self) -> &Self {
+    self
+  }
+}
+
+#[cfg(not(feature = "color"))]
+impl<T: ?Sized> Colorize for T {}
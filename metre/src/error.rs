@@ -1,10 +1,9 @@
 //! List of errors that can happen during the config loading process
 
-use owo_colors::*;
+use crate::color::Colorize;
 use std::convert::Infallible;
 use std::sync::Arc;
 
-#[allow(unused)]
 use crate::LoadLocation;
 
 /// An error that can happen anywhere in the config loading process
@@ -28,7 +27,7 @@ pub enum Error {
     source: Arc<std::io::Error>,
   },
 
-  /// A JSON or JSONC error when deserialzing a partial configuration
+  /// A JSON or JSONC error when deserializing a partial configuration
   #[cfg(any(feature = "json", feature = "jsonc"))]
   #[cfg_attr(docsrs, doc(cfg(any(feature = "json", feature = "jsonc"))))]
   #[error("JSON error loading config from {}", location)]
@@ -38,7 +37,7 @@ pub enum Error {
     location: LoadLocation,
   },
 
-  /// A TOML error when deserialzing a partial configuration
+  /// A TOML error when deserializing a partial configuration
   #[cfg(feature = "toml")]
   #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
   #[error("TOML error loading config from {}", location)]
@@ -48,7 +47,7 @@ pub enum Error {
     location: LoadLocation,
   },
 
-  /// A YAML error when deserialzing a partial configuration
+  /// A YAML error when deserializing a partial configuration
   #[error("YAML error loading config from {}", location)]
   #[cfg(feature = "yaml")]
   #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
@@ -58,6 +57,32 @@ pub enum Error {
     location: LoadLocation,
   },
 
+  /// A JSON, TOML or YAML error at a specific field when deserializing a partial configuration
+  ///
+  /// The message includes the deserializer message with the received value,
+  /// the original [`Error::Json`], [`Error::Toml`] or [`Error::Yaml`] error is kept in the `error` field
+  /// and is not reported as the source to not print the same message twice in error chains
+  #[error("invalid value for config field {} loaded from {}: {}", path.yellow(), location, deserializer_message(error))]
+  InvalidField {
+    /// The deep path to the field that caused the error: eg: my_app.port
+    path: String,
+    location: LoadLocation,
+    /// The original deserialization error
+    error: Box<Error>,
+  },
+
+  /// The format of a configuration could not be detected
+  #[error("unable to detect the format of config from {}", location)]
+  UnknownFormat { location: LoadLocation },
+
+  /// The format is supported by metre but its feature is not enabled, eg: `yaml` without the `yaml` feature
+  #[error("config format {} is not enabled, enable the {} feature of metre to use it", format.yellow(), format.yellow())]
+  UnsupportedFormat { format: String },
+
+  /// A format name could not be parsed
+  #[error(transparent)]
+  ParseFormat(#[from] ParseFormatError),
+
   /// An error loading a partial configuration from an environment variable
   #[cfg(feature = "env")]
   #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
@@ -71,23 +96,199 @@ pub enum Error {
   /// An error when creating a configuration from a partial configuration
   #[error(transparent)]
   FromPartial(#[from] FromPartialError),
+
+  /// Same as [`Error::FromPartial`] but including the env key that loads each missing property,
+  /// see [`ConfigLoader::verbose_missing`](crate::ConfigLoader::verbose_missing)
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[error("{} in finished config {}", from_partial_message(&source.missing_properties, env_keys, &source.conflicting_properties), source.type_name.yellow())]
+  MissingPropertiesVerbose {
+    source: FromPartialError,
+    /// The env key of each missing property, in the same order as [`FromPartialError::missing_properties`]
+    env_keys: Vec<Option<String>>,
+  },
+
+  /// An error returned by the validation function of [`ConfigLoader::finish_validated`](crate::ConfigLoader::finish_validated)
+  #[error("invalid config: {}", message)]
+  Validation { message: String },
+
+  /// An error returned by the closure of [`ConfigLoader::apply`](crate::ConfigLoader::apply)
+  ///
+  /// The original error is kept as the source
+  #[error("error applying changes to config: {}", message)]
+  Apply {
+    /// The message of the original error
+    message: String,
+    #[source]
+    source: Arc<dyn std::error::Error + Send + Sync>,
+  },
+}
+
+impl Error {
+  /// Create an [`Error::Io`] from an I/O error loading a config file
+  pub fn from_io(source: std::io::Error, path: impl Into<String>) -> Self {
+    Error::Io {
+      path: path.into(),
+      source: Arc::new(source),
+    }
+  }
+
+  /// Create an [`Error::Json`] from a JSON deserialization error
+  #[cfg(any(feature = "json", feature = "jsonc"))]
+  #[cfg_attr(docsrs, doc(cfg(any(feature = "json", feature = "jsonc"))))]
+  pub fn from_json(source: serde_json::Error, location: LoadLocation) -> Self {
+    Error::Json {
+      source: Arc::new(source),
+      location,
+    }
+  }
+
+  /// Create an [`Error::Toml`] from a TOML deserialization error
+  #[cfg(feature = "toml")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
+  pub fn from_toml(source: toml::de::Error, location: LoadLocation) -> Self {
+    Error::Toml {
+      source,
+      location,
+    }
+  }
+
+  /// Create an [`Error::Yaml`] from a YAML deserialization error
+  #[cfg(feature = "yaml")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
+  pub fn from_yaml(source: serde_yaml::Error, location: LoadLocation) -> Self {
+    Error::Yaml {
+      source: Arc::new(source),
+      location,
+    }
+  }
+
+  /// Returns true if this is an [`Error::FromPartial`] or [`Error::MissingPropertiesVerbose`] error with at least one missing property
+  pub fn is_missing_properties(&self) -> bool {
+    self.missing_properties().is_some()
+  }
+
+  /// Get the missing properties if this is an [`Error::FromPartial`] or [`Error::MissingPropertiesVerbose`] error
+  ///
+  /// Returns `None` if the error only has conflicting properties
+  pub fn missing_properties(&self) -> Option<&[String]> {
+    let missing = match self {
+      Error::FromPartial(e) => &e.missing_properties,
+      #[cfg(feature = "env")]
+      Error::MissingPropertiesVerbose { source, .. } => &source.missing_properties,
+      _ => return None,
+    };
+
+    if missing.is_empty() {
+      None
+    } else {
+      Some(missing)
+    }
+  }
+
+  /// Get the deep path of the field that caused the error if this is an [`Error::InvalidField`] error
+  pub fn field_path(&self) -> Option<&str> {
+    match self {
+      Error::InvalidField { path, .. } => Some(path),
+      _ => None,
+    }
+  }
+
+  /// Wrap a deserialization error in an [`Error::InvalidField`] if the path points to a field
+  #[allow(unused)]
+  pub(crate) fn at_field(self, path: String, location: &LoadLocation) -> Self {
+    // serde_path_to_error uses "." for errors at the root of the document
+    if path.is_empty() || path == "." {
+      return self;
+    }
+
+    Error::InvalidField {
+      path,
+      location: location.clone(),
+      error: Box::new(self),
+    }
+  }
+
+  /// Get the source I/O error if this is an [`Error::Io`] error
+  pub fn as_io(&self) -> Option<&std::io::Error> {
+    match self {
+      Error::Io { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+
+  /// Get the source network error if this is an [`Error::Network`] error
+  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+  #[cfg_attr(docsrs, doc(cfg(any(feature = "url-blocking", feature = "url-async"))))]
+  pub fn as_network(&self) -> Option<&reqwest::Error> {
+    match self {
+      Error::Network { source, .. } => Some(source),
+      _ => None,
+    }
+  }
+}
+
+fn missing_with_env_keys(properties: &[String], env_keys: &[Option<String>]) -> String {
+  properties
+    .iter()
+    .enumerate()
+    .map(|(i, prop)| match env_keys.get(i) {
+      Some(Some(key)) => format!("{} (env: {})", prop.yellow(), key.yellow()),
+      _ => prop.yellow().to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// The message of the deserializer error wrapped in an [`Error::InvalidField`]
+fn deserializer_message(error: &Error) -> String {
+  match std::error::Error::source(error) {
+    Some(source) => source.to_string(),
+    None => error.to_string(),
+  }
 }
 
 /// Error produced when merging two partial configurations
+///
+/// Use [`MergeError::new`] to create it outside of metre, more fields can be added in the future
 #[derive(Debug, Clone, thiserror::Error)]
 #[error("error merging config field {}: {}", field.yellow(), message)]
+#[non_exhaustive]
 pub struct MergeError {
   /// The deep path to the field that caused the error: eg: my_app.port
   pub field: String,
   /// The error message from the merge function
   pub message: String,
+  /// The original error of the merge function, if it implements [`std::error::Error`]
+  #[source]
+  pub source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl MergeError {
+  /// Create a merge error for a field without a source error
+  pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      field: field.into(),
+      message: message.into(),
+      source: None,
+    }
+  }
+
+  /// Set the original error of the merge function
+  pub fn with_source(mut self, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+    self.source = Some(Arc::new(source));
+    self
+  }
 }
 
 /// Error parsing a value from an environment variable
 #[cfg(feature = "env")]
 #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+///
+/// Use [`FromEnvError::new`] to create it outside of metre, more fields can be added in the future
 #[derive(Debug, Clone, thiserror::Error)]
 #[error("error parsing var {} from env for field: {}: {}", key.yellow(), field.yellow(), message)]
+#[non_exhaustive]
 pub struct FromEnvError {
   /// The env key that produced the error: eg: MY_APP_PORT
   pub key: String,
@@ -95,18 +296,193 @@ pub struct FromEnvError {
   pub field: String,
   /// The error message from the parsing function
   pub message: String,
+  /// The original error of the parsing function, if it implements [`std::error::Error`]
+  #[source]
+  pub source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+#[cfg(feature = "env")]
+impl FromEnvError {
+  /// Create an env error for a key and field without a source error
+  pub fn new(key: impl Into<String>, field: impl Into<String>, message: impl Into<String>) -> Self {
+    Self {
+      key: key.into(),
+      field: field.into(),
+      message: message.into(),
+      source: None,
+    }
+  }
+
+  /// Set the original error of the parsing function
+  pub fn with_source(mut self, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+    self.source = Some(Arc::new(source));
+    self
+  }
+}
+
+/// Error returned by [`ConfigLoader::check_env`](crate::ConfigLoader::check_env)
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum CheckEnvError {
+  /// Some required properties are not set by the env variables nor by the defaults
+  #[error("missing properties {}", properties.iter().map(|name| name.yellow().to_string()).collect::<Vec<_>>().join(", "))]
+  Missing {
+    /// The deep path of each missing property: eg: ["my_app.port"]
+    properties: Vec<String>,
+  },
+
+  /// An env variable is set but its value cannot be parsed
+  #[error(transparent)]
+  FromEnv(#[from] FromEnvError),
+
+  /// The env partial config cannot be merged with the defaults
+  #[error(transparent)]
+  Merge(#[from] MergeError),
+}
+
+#[cfg(feature = "env")]
+impl CheckEnvError {
+  /// Get the missing properties if this is a [`CheckEnvError::Missing`] error
+  pub fn missing_properties(&self) -> Option<&[String]> {
+    match self {
+      CheckEnvError::Missing { properties } => Some(properties),
+      _ => None,
+    }
+  }
+}
+
+/// Error reading or parsing a `.env` file
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DotenvError {
+  /// An I/O error reading the `.env` file
+  #[error("I/O error loading env file {}", path.yellow())]
+  Io {
+    path: String,
+    #[source]
+    source: Arc<std::io::Error>,
+  },
+
+  /// A syntax error in the `.env` file
+  #[error("error parsing env file {} at line {}: {}", path.yellow(), line, message)]
+  Parse {
+    path: String,
+    /// The 1-based line number of the error
+    line: usize,
+    message: String,
+  },
+}
+
+/// Error produced by one of the providers of a [`ChainEnv`](crate::ChainEnv)
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+#[derive(Debug, Clone)]
+pub enum ChainEnvError<A, B> {
+  /// An error from the first provider
+  First(A),
+  /// An error from the second provider
+  Second(B),
+}
+
+#[cfg(feature = "env")]
+impl<A: std::fmt::Display, B: std::fmt::Display> std::fmt::Display for ChainEnvError<A, B> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ChainEnvError::First(e) => e.fmt(f),
+      ChainEnvError::Second(e) => e.fmt(f),
+    }
+  }
+}
+
+#[cfg(feature = "env")]
+impl<A, B> std::error::Error for ChainEnvError<A, B>
+where
+  A: std::error::Error + 'static,
+  B: std::error::Error + 'static,
+{
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ChainEnvError::First(e) => Some(e),
+      ChainEnvError::Second(e) => Some(e),
+    }
+  }
+}
+
+/// Error parsing a [`Format`](crate::Format) from a string
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("unknown or disabled config format {}", name.yellow())]
+pub struct ParseFormatError {
+  /// The name that was not recognized
+  pub name: String,
 }
 
 /// Error produced when creating a config from a partial config
+///
+/// Use [`FromPartialError::new`] to create it outside of metre, more fields can be added in the future
 #[derive(Debug, Clone, thiserror::Error)]
-#[error("missing properties {} in finished config", missing_properties.iter().map(|name| name.yellow().to_string()).collect::<Vec<_>>().join(", ") )]
+#[error("{} in finished config {}", from_partial_message(missing_properties, &[], conflicting_properties), type_name.yellow())]
+#[non_exhaustive]
 pub struct FromPartialError {
+  /// The name of the top level config type that was being created: eg: my_app::MyConfig
+  pub type_name: &'static str,
+
   /// The list of properties that are required but missing
   ///
   /// This will include the full path to the properties: eg: ["my_app.port"] for nested configurations
   ///
   /// Or just ["port"] for not nested configurations
   pub missing_properties: Vec<String>,
+
+  /// The list of pairs of properties that cannot be present at the same time, see the `conflicts_with` attribute
+  ///
+  /// This will include the full path to the properties like in [`Self::missing_properties`]
+  pub conflicting_properties: Vec<(String, String)>,
+}
+
+impl FromPartialError {
+  /// Create an error for the config type `type_name` with the given missing properties and no conflicts
+  pub fn new(type_name: &'static str, missing_properties: Vec<String>) -> Self {
+    Self {
+      type_name,
+      missing_properties,
+      conflicting_properties: vec![],
+    }
+  }
+
+  /// Set the pairs of properties that cannot be present at the same time
+  pub fn with_conflicting_properties(mut self, conflicting_properties: Vec<(String, String)>) -> Self {
+    self.conflicting_properties = conflicting_properties;
+    self
+  }
+}
+
+fn from_partial_message(
+  missing_properties: &[String],
+  env_keys: &[Option<String>],
+  conflicting_properties: &[(String, String)],
+) -> String {
+  let mut parts = vec![];
+  if !missing_properties.is_empty() {
+    parts.push(format!(
+      "missing properties {}",
+      missing_with_env_keys(missing_properties, env_keys)
+    ));
+  }
+
+  if !conflicting_properties.is_empty() {
+    parts.push(format!(
+      "conflicting properties {}",
+      conflicting_properties
+        .iter()
+        .map(|(a, b)| format!("{} and {}", a.yellow(), b.yellow()))
+        .collect::<Vec<_>>()
+        .join(", ")
+    ));
+  }
+
+  parts.join(", ")
 }
 
 macro_rules! impl_from_infallible {
@@ -132,3 +508,90 @@ impl_from_infallible!(
 impl_from_infallible!(
   FromEnvError
 );
+
+/// A failure report returned by [`ConfigLoader::finish_or_report`](crate::ConfigLoader::finish_or_report)
+///
+/// Implements [`miette::Diagnostic`], each missing property is a related diagnostic with a suggestion to fix it,
+/// use it with [`miette::Report`] or as the error of a `fn main() -> miette::Result<()>` to get a multi-line rendering
+#[cfg(feature = "miette")]
+#[cfg_attr(docsrs, doc(cfg(feature = "miette")))]
+#[derive(Debug, Clone)]
+pub struct Report {
+  /// The error returned by [`ConfigLoader::finish`](crate::ConfigLoader::finish)
+  pub error: Error,
+  /// The missing properties of the config with their env keys, empty if the error is not about missing properties
+  pub missing: Vec<MissingProperty>,
+}
+
+#[cfg(feature = "miette")]
+impl std::fmt::Display for Report {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.missing.len() {
+      0 => self.error.fmt(f),
+      1 => write!(f, "the config is missing 1 required property"),
+      n => write!(f, "the config is missing {n} required properties"),
+    }
+  }
+}
+
+#[cfg(feature = "miette")]
+impl std::error::Error for Report {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    if self.missing.is_empty() {
+      std::error::Error::source(&self.error)
+    } else {
+      None
+    }
+  }
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for Report {
+  fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+    if self.missing.is_empty() {
+      None
+    } else {
+      Some(Box::new("metre::missing_properties"))
+    }
+  }
+
+  fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+    if self.missing.is_empty() {
+      None
+    } else {
+      Some(Box::new("set the missing properties in a config file or with their env variables"))
+    }
+  }
+
+  fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn miette::Diagnostic> + 'a>> {
+    if self.missing.is_empty() {
+      None
+    } else {
+      Some(Box::new(self.missing.iter().map(|item| item as &dyn miette::Diagnostic)))
+    }
+  }
+}
+
+/// A missing property in a [`Report`]
+#[cfg(feature = "miette")]
+#[cfg_attr(docsrs, doc(cfg(feature = "miette")))]
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("missing property {}", property.yellow())]
+pub struct MissingProperty {
+  /// The deep path to the property: eg: my_app.port
+  pub property: String,
+  /// The env key that can be used to set the property, if it can be loaded from env
+  pub env_key: Option<String>,
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for MissingProperty {
+  fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+    let help = match &self.env_key {
+      Some(key) => format!("add `{}` to a config file or set the `{}` env variable", self.property, key),
+      None => format!("add `{}` to a config file", self.property),
+    };
+
+    Some(Box::new(help))
+  }
+}
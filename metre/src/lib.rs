@@ -53,16 +53,20 @@
 //! # }
 //! ``` 
 
-use owo_colors::*;
+use color::Colorize;
 use serde::de::DeserializeOwned;
 use std::fmt::Display;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+#[cfg(any(feature = "url-blocking", feature = "url-async"))]
+use std::time::Duration;
 #[cfg(feature = "env")]
 use std::{env::VarError, collections::{BTreeMap, HashMap}};
 #[allow(unused)]
 use std::convert::Infallible;
 
+mod color;
 pub mod error;
 pub mod merge;
 pub mod parse;
@@ -82,28 +86,46 @@ pub use error::Error;
 ///
 /// The [`PartialConfig`] generated type is a deep-partial version of the struct
 ///
+/// Generic structs are supported, eg: `struct Component<T: Config>` with a `#[config(nested)] inner: T` field,
+/// the generated PartialConfig struct has the same type parameters and where clauses
+///
 /// See the [`Config`] and [`PartialConfig`] documentation for more information on the available methods.
 ///
 /// # Container Attributes
 /// | Attribute | Description | Default | Example | Observations |
 /// | --- | --- | --- | --- | --- |
-/// | rename_all | The case conversion to apply to all fields | none | `#[config(rename_all = "snake_case")]` | This will apply `#[serde(rename_all)]` to the PartialConfig struct |
+/// | rename_all | The case conversion to apply to all fields | none | `#[config(rename_all = "snake_case")]` | This will apply `#[serde(rename_all)]` to the PartialConfig struct, the auto calculated env keys are derived from the renamed field names, fields are renamed exactly like serde does, so `"lowercase"` and `"snake_case"` leave the field names unchanged, eg: a `maxConnections` field with `"lowercase"` is read from `MAX_CONNECTIONS` |
+/// | rename_all_env | The case conversion to apply to all fields env keys | `"SCREAMING_SNAKE_CASE"` | `#[config(rename_all_env = "kebab-case")]` | This only affects the auto calculated env keys, they are derived from the field names (or the `rename` attribute) ignoring `rename_all` |
 /// | skip_env | If applied, this struct will not load anything from env variables | false | `#[config(skip_env)]` |
-/// | env_prefix | The prefix to use for all fields environment variables | "{}" | `#[config(env_prefix = "{}MY_APP_")]` | Almost always you'll want to include the `{}` placeholder like `"{}MY_APP"` to allow auto generated prefixes to work, if not the env key will be fixed to the value of the attribute |
+/// | env_prefix | The prefix to use for all fields environment variables | "{}" | `#[config(env_prefix = "{}MY_APP_")]` | Almost always you'll want to include the `{}` placeholder like `"{}MY_APP"` to allow auto generated prefixes to work, if not the env key will be fixed to the value of the attribute, an empty string means no prefix at all, the fields are read from their plain keys like `PORT` and the prefix passed to the loader is ignored |
+/// | env_strict | Error when loading from env if there are variables under the computed env prefix that are not loaded by any field | false | `#[config(env_strict)]` | Only works with [`EnvProvider`]'s that can list their keys, see [`EnvProvider::keys`], nested configurations keys are also considered known, the check is skipped if the computed prefix is empty |
+/// | trim_env | Trim the leading and trailing whitespace of the env values before parsing them | false | `#[config(trim_env)]` | Applies to all the fields of the struct, it can be disabled per field with `#[config(trim_env = false)]`, nested configurations use their own attribute |
+/// | default_nested | Make [`PartialConfig::defaults`] return the defaults of the optional nested fields even if they are empty | false | `#[config(default_nested)]` | Applies to all the `Option<T>` nested fields of the struct, it can be disabled per field with `#[config(default_nested = false)]`, by default an optional nested configuration is `None` in the defaults unless some of its fields have a default value |
 /// | allow_unknown_fields | Allow unknown fields in deserialization of the PartialConfig type | false | `#[config(allow_unknown_fields)]` | By default metre will add a `#[serde(deny_unknown_fields)]` to the Partial definition, use this attribute if you want to override this behavior |
 /// | parial_name | The name of the generated PartialConfig struct | `Partial{StructName}` | `#[config(partial_name = PartialMyConfig)] | rename the PartialConfig generated struct, the PartialConfig struct will have the same visibility as the struct |
+/// | partial_derive | Additional traits to derive for the generated PartialConfig struct | - | `#[config(partial_derive(Clone, PartialEq))]` | The partial struct always derives `Debug`, `Default`, `Serialize` and `Deserialize`, nested configurations must derive the same traits for their partial structs, deriving `Clone` is required by [`ConfigLoader::finish_ref`] |
 /// | crate | Rename the metre crate in the generated derive code | `metre` | `#[config(crate = other)]` | This is almost only useful for internal unit tests |
 ///
 /// # Field Attributes
 /// | Attribute | Description | Default | Example | Observations |
 /// | --- | --- | --- | --- | --- |
-/// | env | The name of the environment variable to use for this field | `"{}PROPERTY_NAME"` | `#[config(env = "{}PORT")]` | The default value of the attribute is the SCREAMING_SNAKE_CASE version of the field name after applying rename and rename_all configurations, and the `{}` placeholder is filled with the auto calculated env prefix |
+/// | env | The name of the environment variable to use for this field | `"{}PROPERTY_NAME"` | `#[config(env = "{}PORT")]` | The default value of the attribute is the SCREAMING_SNAKE_CASE version of the field name after applying rename and rename_all configurations, and every `{}` placeholder is filled with the auto calculated env prefix, use `{{` and `}}` for literal braces and a format without placeholders for a fixed key, `env = false` is a shorthand for `skip_env` |
 /// | skip_env | If applied, this field will not load from env variables | false | `#[config(skip_env)]` | This attribute has precedence over the skip_env attribute in the container |
-/// | parse_env | The name of the function to use to parse the value from the environment variable | `FromStr::from_str` | `#[config(parse_env = parse_fn)]` | The function must have the signature `fn(&str) -> Result<Option<T>, E>` where `T` is the type of the field and `E` is any error that implements Display, see the [`parse`] module to see utility functions that can be used here |
-/// | merge | The name of the function to use to merge two values of this field | - | `#[config(merge = merge_fn)]` | The function must have the signature `fn(&mut Option<T>, Option<T>) -> Result<(), E>` where `T` is the type of the field and `E` is any error that implements Display, see the [`merge`] module to find utility functions that can be used here, the default implementation replaces the previous value with the next, if it is present in the new added stage |
-/// | default | The default value to use for this field | none | `#[config(default = 3000)]` | The default value must be of the same type as the field, if the field is an Option, the default value must be of the same type as the inner type of the Option, the [`Default::default`] implementation of the Partial struct will not use this value, to get the values defined with this attribute use [`PartialConfig::defaults`] |
-/// | flatten | If applied, this field will be merged with the previous stage instead of replacing it | false | `#[config(flatten)]` | This attribute will apply a `#[serde(flatten)]` to the PartialConfig struct, it will also modify the calculated env key prefix for nested fields |
-/// | nested | If applied, this field will be treated as a nested configuration | false | `#[config(nested)]` | This attrbute indicates that this field is a nested partial configuration, the nested field must also implement the [`Config`] trait |
+/// | conflicts_with | The name of another field that cannot be present at the same time as this field | - | `#[config(conflicts_with = "password_file")]` | If both fields have a value the config cannot be finished and both fields are reported in [`FromPartialError::conflicting_properties`](error::FromPartialError::conflicting_properties), use the Rust name of the other field |
+/// | deprecated | Mark the field as deprecated, with an optional note | - | `#[config(deprecated = "use listen instead")]` | With the `tracing` feature a `warn!` event is emitted for every stage that sets the field, see [`PartialConfig::list_deprecated_properties`] |
+/// | requires | The name of another field that is required when this field is present | - | `#[config(requires = "tls_key")]` | If this field has a value and the other field doesn't, the other field is reported as a missing property, use the Rust name of the other field |
+/// | trim_env | Trim the leading and trailing whitespace of the env value before parsing it | the container `trim_env` | `#[config(trim_env = false)]` | Overrides the `trim_env` attribute of the container for this field |
+/// | default_nested | Make [`PartialConfig::defaults`] return `Some` with the defaults of this optional nested field | the container `default_nested` | `#[config(nested, default_nested)]` | Only valid in `Option<T>` nested fields, a present block is finished as `Some` even if it is empty, if the nested configuration has required fields without defaults they are reported as missing unless other stage sets them |
+/// | env_bool_presence | Set a `bool` field to `true` if its env variable is present, whatever its value is | false | `#[config(env_bool_presence)]` | Useful for flag-style variables like `VERBOSE=`, if the variable is not set the field is left unset, it cannot be combined with `parse_env` |
+/// | parse_env | The name of the function to use to parse the value from the environment variable | `FromStr::from_str` | `#[config(parse_env = parse_fn)]` | The function must have the signature `fn(&str) -> Result<Option<T>, E>` where `T` is the type of the field and `E` is any error that implements Display, if `E` also implements [`std::error::Error`] it is kept as the source of the [`FromEnvError`], see the [`parse`] module to see utility functions that can be used here |
+/// | merge | The name of the function to use to merge two values of this field | - | `#[config(merge = merge_fn)]` | The function must have the signature `fn(&mut Option<T>, Option<T>) -> Result<(), E>` where `T` is the type of the field and `E` is any error that implements Display, if `E` also implements [`std::error::Error`] it is kept as the source of the [`MergeError`], if `E` is a [`MergeError`] its `field` is a sub-path that is joined to the field path, see the [`merge`] module to find utility functions that can be used here, the default implementation replaces the previous value with the next, if it is present in the new added stage |
+/// | default | The default value to use for this field | none | `#[config(default = 3000)]` | The value can be any expression, like `crate::DEFAULT_PORT`, `DEFAULT_PORT + 1` or `default_host()`, it is evaluated each time [`PartialConfig::defaults`] is called, the default value must be of the same type as the field, if the field is an Option, the default value must be of the same type as the inner type of the Option, the [`Default::default`] implementation of the Partial struct will not use this value, to get the values defined with this attribute use [`PartialConfig::defaults`] |
+/// | flatten | If applied, this field will be merged with the previous stage instead of replacing it | false | `#[config(flatten)]` | This attribute will apply a `#[serde(flatten)]` to the PartialConfig struct, it will also modify the calculated env key prefix for nested fields, when combined with `nested` the nested fields are at the top level in files, their env keys inherit the container prefix without the field name and their missing properties are reported without the field name |
+/// | nested | If applied, this field will be treated as a nested configuration | false | `#[config(nested)]` | This attrbute indicates that this field is a nested partial configuration, the nested field must also implement the [`Config`] trait, it can also be applied to `Option<T>`, `Box<T>`, `Arc<T>`, `Rc<T>` and `Vec<T>` fields, missing properties of list items are reported with indexed paths like `servers[0].port` |
+/// | skip | If applied, this field will not be loaded from any source | false | `#[config(skip)]` | The field is excluded from the PartialConfig struct and it is initialized with the `default` attribute value if present or with [`Default::default`] otherwise, it can only be combined with the `default` attribute |
+/// | flatten_extra | If applied, this map field will collect all the unknown keys of the configuration | false | `#[config(flatten_extra)]` | The field must be a map like `HashMap<String, serde_json::Value>`, this attribute will apply a `#[serde(flatten)]` to the PartialConfig struct and disable `#[serde(deny_unknown_fields)]`, the maps of different stages are merged by extending the previous one, it cannot be combined with other attributes and it is never loaded from env variables |
+/// | env_seq | If applied, this nested list field will be loaded from indexed env variables | false | `#[config(nested, env_seq)]` | The number of items is read from `{PREFIX}FIELD_NAME_COUNT` and each item is loaded with the `{PREFIX}FIELD_NAME_{INDEX}_` prefix, eg: `SERVERS_COUNT=2`, `SERVERS_0_PORT=8080`, `SERVERS_1_PORT=8081`, if the count variable is missing the list is not loaded from env, it must be combined with `nested` and it cannot be used in an item with `env_strict` |
+/// | coerce_strings | If applied, this field will also accept a quoted string in files and parse it with [`FromStr`](std::str::FromStr), like the values loaded from env | false | `#[config(coerce_strings)]` | Useful for numeric and boolean fields where the value may end up quoted, eg: `port = "3000"` in TOML or `port: "3000"` in YAML, it cannot be combined with `nested` or `flatten` |
 /// | rename | The rename the field in the partial configuration | - | `#[config(rename = "other_name")]` | This will apply a `#[serde(rename)]` attribute to the Partial struct, it will also modify the auto calculated env key for the field |
 #[cfg(feature = "derive")]
 #[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
@@ -153,6 +175,36 @@ pub trait PartialConfig: DeserializeOwned + Default {
   /// Returns true if this partial configuration has no values
   fn is_empty(&self) -> bool;
 
+  /// List of properties that have a value in this partial configuration
+  ///
+  /// This will include the full path to the properties: eg: ["my_app.port"] for nested configurations
+  ///
+  /// The default implementation returns an empty list, the [`Config`](macro@Config) derive macro will generate the full list
+  fn list_present_properties(&self) -> Vec<String> {
+    vec![]
+  }
+
+  /// List of the doc comments of the fields of this partial configuration as `(path, doc)` pairs
+  ///
+  /// This will include the full path to the properties: eg: `("my_app.port", "The port to listen on")` for nested configurations,
+  /// fields without doc comments are not included
+  ///
+  /// The default implementation returns an empty list, the [`Config`](macro@Config) derive macro will generate the full list
+  fn field_docs() -> Vec<(String, String)> {
+    vec![]
+  }
+
+  /// List of the fields marked with `#[config(deprecated)]` that are present in this partial configuration
+  /// as `(path, note)` pairs, the note is empty if it was not set
+  ///
+  /// This will include the full path to the properties like in [`Self::list_present_properties`], with the `tracing` feature
+  /// a warning is emitted for every stage that sets one of these fields
+  ///
+  /// The default implementation returns an empty list, the [`Config`](macro@Config) derive macro will generate the full list
+  fn list_deprecated_properties(&self) -> Vec<(String, String)> {
+    vec![]
+  }
+
   /// Create a partial configuration from environment variables
   /// [`EnvProvider`] is specially usefull for unit tests and is already implemented for several
   /// types of [HashMap]'s and [BTreeMap]'s from the standard library
@@ -163,6 +215,24 @@ pub trait PartialConfig: DeserializeOwned + Default {
     prefix: Option<&str>,
   ) -> Result<Self, FromEnvError>;
 
+  /// Same as [`Self::from_env_with_provider_and_optional_prefix`] but replacing the `#[config(env_prefix)]` format of the container at runtime
+  ///
+  /// The `{}` placeholder in `env_prefix_fmt` is filled with `prefix` (or with an empty string if `prefix` is `None`),
+  /// and if `env_prefix_fmt` is `None` the format defined in the attribute is used.
+  /// Nested configurations keep using their own attributes
+  ///
+  /// The default implementation ignores `env_prefix_fmt`, the [`Config`](macro@Config) derive macro will generate the full implementation
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn from_env_with_provider_and_env_prefix_fmt<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+    env_prefix_fmt: Option<&str>,
+  ) -> Result<Self, FromEnvError> {
+    let _ = env_prefix_fmt;
+    Self::from_env_with_provider_and_optional_prefix(env, prefix)
+  }
+
   /// Forwards to [`Self::from_env_with_provider_and_optional_prefix`]
   #[cfg(feature = "env")]
   #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
@@ -193,6 +263,40 @@ pub trait PartialConfig: DeserializeOwned + Default {
   fn from_env() -> Result<Self, FromEnvError> {
     Self::from_env_with_provider_and_optional_prefix(&StdEnv, None)
   }
+
+  /// List of the environment variables keys that this partial configuration will read from, including nested configurations
+  ///
+  /// The default implementation returns an empty list, the [`Config`](macro@Config) derive macro will generate the full list
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_keys_with_optional_prefix(prefix: Option<&str>) -> Vec<String> {
+    let _ = prefix;
+    vec![]
+  }
+
+  /// Get the environment variable key that loads a property, the property is a deep path as returned by [`Self::list_missing_properties`]
+  ///
+  /// Returns `None` if the property is not loaded from env variables
+  ///
+  /// The default implementation returns `None`, the [`Config`](macro@Config) derive macro will generate the full implementation
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn property_env_key(property: &str, prefix: Option<&str>) -> Option<String> {
+    let _ = (property, prefix);
+    None
+  }
+
+  /// Description of the environment variables that this partial configuration will read from, including nested configurations
+  ///
+  /// This is the same list of [`Self::env_keys_with_optional_prefix`] with the field path, if the property is required and the field doc comment
+  ///
+  /// The default implementation returns an empty list, the [`Config`](macro@Config) derive macro will generate the full list
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_vars_with_optional_prefix(prefix: Option<&str>) -> Vec<EnvVar> {
+    let _ = prefix;
+    vec![]
+  }
 }
 
 impl<T: Config> Config for Option<T> {
@@ -253,6 +357,24 @@ impl<T: PartialConfig> PartialConfig for Option<T> {
     }
   }
 
+  fn list_present_properties(&self) -> Vec<String> {
+    match self {
+      None => vec![],
+      Some(me) => me.list_present_properties(),
+    }
+  }
+
+  fn field_docs() -> Vec<(String, String)> {
+    T::field_docs()
+  }
+
+  fn list_deprecated_properties(&self) -> Vec<(String, String)> {
+    match self {
+      None => vec![],
+      Some(me) => me.list_deprecated_properties(),
+    }
+  }
+
   #[cfg(feature = "env")]
   #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
   fn from_env_with_provider_and_optional_prefix<E: EnvProvider>(
@@ -266,6 +388,250 @@ impl<T: PartialConfig> PartialConfig for Option<T> {
       Ok(Some(v))
     }
   }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn from_env_with_provider_and_env_prefix_fmt<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+    env_prefix_fmt: Option<&str>,
+  ) -> Result<Self, FromEnvError> {
+    let v = T::from_env_with_provider_and_env_prefix_fmt(env, prefix, env_prefix_fmt)?;
+    if v.is_empty() {
+      Ok(None)
+    } else {
+      Ok(Some(v))
+    }
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_keys_with_optional_prefix(prefix: Option<&str>) -> Vec<String> {
+    T::env_keys_with_optional_prefix(prefix)
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn property_env_key(property: &str, prefix: Option<&str>) -> Option<String> {
+    T::property_env_key(property, prefix)
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_vars_with_optional_prefix(prefix: Option<&str>) -> Vec<EnvVar> {
+    // the properties of an optional nested configuration are never required
+    T::env_vars_with_optional_prefix(prefix)
+      .into_iter()
+      .map(|var| EnvVar {
+        required: false,
+        ..var
+      })
+      .collect()
+  }
+}
+
+impl<T: Config> Config for Box<T> {
+  type Partial = Box<T::Partial>;
+  fn from_partial(partial: Self::Partial) -> Result<Self, FromPartialError> {
+    let v = T::from_partial(*partial)?;
+    Ok(Box::new(v))
+  }
+}
+
+/// Shared nested configurations use the partial configuration of the inner type,
+/// the final configuration is wrapped in an [`Arc`] in [`Config::from_partial`]
+impl<T: Config> Config for Arc<T> {
+  type Partial = T::Partial;
+  fn from_partial(partial: Self::Partial) -> Result<Self, FromPartialError> {
+    let v = T::from_partial(partial)?;
+    Ok(Arc::new(v))
+  }
+}
+
+/// Same as the [`Arc`] implementation but for [`Rc`]
+impl<T: Config> Config for Rc<T> {
+  type Partial = T::Partial;
+  fn from_partial(partial: Self::Partial) -> Result<Self, FromPartialError> {
+    let v = T::from_partial(partial)?;
+    Ok(Rc::new(v))
+  }
+}
+
+/// A boxed nested partial configuration, useful for large or recursive configurations
+///
+/// All methods are forwarded to the inner partial configuration, recursive fields like `Option<Box<Self>>`
+/// should use `#[config(skip_env)]` to avoid reading environment variables endlessly
+impl<T: PartialConfig> PartialConfig for Box<T> {
+  fn defaults() -> Self {
+    Box::new(T::defaults())
+  }
+
+  fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+    T::merge(self, *other)
+  }
+
+  fn list_missing_properties(&self) -> Vec<String> {
+    T::list_missing_properties(self)
+  }
+
+  fn is_empty(&self) -> bool {
+    T::is_empty(self)
+  }
+
+  fn list_present_properties(&self) -> Vec<String> {
+    T::list_present_properties(self)
+  }
+
+  fn field_docs() -> Vec<(String, String)> {
+    T::field_docs()
+  }
+
+  fn list_deprecated_properties(&self) -> Vec<(String, String)> {
+    T::list_deprecated_properties(self)
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn from_env_with_provider_and_optional_prefix<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+  ) -> Result<Self, FromEnvError> {
+    let v = T::from_env_with_provider_and_optional_prefix(env, prefix)?;
+    Ok(Box::new(v))
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn from_env_with_provider_and_env_prefix_fmt<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+    env_prefix_fmt: Option<&str>,
+  ) -> Result<Self, FromEnvError> {
+    let v = T::from_env_with_provider_and_env_prefix_fmt(env, prefix, env_prefix_fmt)?;
+    Ok(Box::new(v))
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_keys_with_optional_prefix(prefix: Option<&str>) -> Vec<String> {
+    T::env_keys_with_optional_prefix(prefix)
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn property_env_key(property: &str, prefix: Option<&str>) -> Option<String> {
+    T::property_env_key(property, prefix)
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn env_vars_with_optional_prefix(prefix: Option<&str>) -> Vec<EnvVar> {
+    T::env_vars_with_optional_prefix(prefix)
+  }
+}
+
+impl<T: Config> Config for Vec<T> {
+  type Partial = Vec<T::Partial>;
+  fn from_partial(partial: Self::Partial) -> Result<Self, FromPartialError> {
+    let mut target = Vec::with_capacity(partial.len());
+    for (i, inner) in partial.into_iter().enumerate() {
+      let v = T::from_partial(inner).map_err(|e| FromPartialError {
+        type_name: std::any::type_name::<Self>(),
+        missing_properties: e
+          .missing_properties
+          .into_iter()
+          .map(|prop| format!("[{}].{}", i, prop))
+          .collect(),
+        conflicting_properties: e
+          .conflicting_properties
+          .into_iter()
+          .map(|(a, b)| (format!("[{}].{}", i, a), format!("[{}].{}", i, b)))
+          .collect(),
+      })?;
+      target.push(v);
+    }
+
+    Ok(target)
+  }
+}
+
+/// A list of nested partial configurations
+///
+/// Merging two lists will replace the previous list with the next one if the next one is not empty,
+/// and lists are only loaded from environment variables with the `#[config(env_seq)]` field attribute
+impl<T: PartialConfig> PartialConfig for Vec<T> {
+  fn defaults() -> Self {
+    vec![]
+  }
+
+  fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+    if !other.is_empty() {
+      *self = other;
+    }
+
+    Ok(())
+  }
+
+  fn list_missing_properties(&self) -> Vec<String> {
+    let mut missing = vec![];
+    for (i, item) in self.iter().enumerate() {
+      for prop in item.list_missing_properties() {
+        missing.push(format!("[{}].{}", i, prop));
+      }
+    }
+
+    missing
+  }
+
+  fn is_empty(&self) -> bool {
+    <[T]>::is_empty(self)
+  }
+
+  fn list_present_properties(&self) -> Vec<String> {
+    let mut present = vec![];
+    for (i, item) in self.iter().enumerate() {
+      for prop in item.list_present_properties() {
+        present.push(format!("[{}].{}", i, prop));
+      }
+    }
+
+    present
+  }
+
+  fn list_deprecated_properties(&self) -> Vec<(String, String)> {
+    let mut deprecated = vec![];
+    for (i, item) in self.iter().enumerate() {
+      for (prop, note) in item.list_deprecated_properties() {
+        deprecated.push((format!("[{}].{}", i, prop), note));
+      }
+    }
+
+    deprecated
+  }
+
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  fn from_env_with_provider_and_optional_prefix<E: EnvProvider>(
+    _env: &E,
+    _prefix: Option<&str>,
+  ) -> Result<Self, FromEnvError> {
+    Ok(vec![])
+  }
+}
+
+/// Description of an environment variable read by a partial configuration, see [`PartialConfig::env_vars_with_optional_prefix`]
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct EnvVar {
+  /// The env key: eg: MY_APP_PORT
+  pub key: String,
+  /// The deep path to the property: eg: my_app.port
+  pub field: String,
+  /// True if the property is required in the final configuration and doesn't have a default value
+  pub required: bool,
+  /// The doc comment of the field, or an empty string if the field is not documented
+  pub doc: String,
 }
 
 /// Implement this trait if you want to load a configuration from custom environment variables
@@ -282,6 +648,13 @@ pub trait EnvProvider {
   ///
   /// If the variable is not present, implementations should return `Ok(None)`
   fn get(&self, key: &str) -> Result<Option<String>, Self::Error>;
+
+  /// List all the variables present in the enviroment
+  ///
+  /// Implementations that cannot enumerate their variables should return `None`, this is the default implementation
+  fn keys(&self) -> Option<Vec<String>> {
+    None
+  }
 }
 
 #[cfg(feature = "env")]
@@ -293,6 +666,10 @@ macro_rules! impl_env_provider_for_map {
       fn get(&self, key: &str) -> Result<Option<String>, Self::Error> {
         Ok(self.get(key).map(ToString::to_string))
       }
+
+      fn keys(&self) -> Option<Vec<String>> {
+        Some(self.keys().map(ToString::to_string).collect())
+      }
     }
   };
 }
@@ -333,172 +710,1422 @@ impl EnvProvider for StdEnv {
       Ok(v) => Ok(Some(v)),
     }
   }
+
+  fn keys(&self) -> Option<Vec<String>> {
+    // non unicode keys can never be read with [`EnvProvider::get`] so they are skipped
+    let keys = std::env::vars_os()
+      .filter_map(|(key, _)| key.into_string().ok())
+      .collect();
+
+    Some(keys)
+  }
 }
 
-/// A location from where a configuration was loaded
+/// An implementation of [`EnvProvider`] that reads from a `.env` file
 ///
-/// can be from Memory, File, or URL
+/// The file is read and parsed once when the provider is created, not on every lookup,
+/// so I/O and syntax errors are reported by [`DotenvFileEnv::new`] and lookups never fail
+///
+/// Lines have the form `KEY=value`, empty lines and lines starting with `#` are ignored,
+/// an optional `export ` prefix is allowed and values can be wrapped in single or double quotes,
+/// double quoted values support the `\n`, `\r`, `\t`, `\\` and `\"` escapes,
+/// quoted and unquoted values can be followed by a ` # comment`
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum LoadLocation {
-  Memory,
-  File(String),
-  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
-  #[cfg_attr(docsrs, doc(cfg(any(feature = "url-blocking", feature = "url-async"))))]
-  Url(String),
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+pub struct DotenvFileEnv {
+  vars: BTreeMap<String, String>,
 }
 
-impl Display for LoadLocation {
-  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    use LoadLocation::*;
-    match self {
-      Memory => write!(f, "{}", "memory".yellow()),
-      File(location) => write!(f, "file: {}", location.yellow()),
-      #[cfg(any(feature = "url-blocking", feature = "url-async"))]
-      Url(location) => write!(f, "url: {}", location.yellow()),
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+impl DotenvFileEnv {
+  /// Read and parse a `.env` file
+  pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, error::DotenvError> {
+    let path = path.as_ref();
+    let code = std::fs::read_to_string(path).map_err(|e| error::DotenvError::Io {
+      path: path.display().to_string(),
+      source: Arc::new(e),
+    })?;
+
+    let mut vars = BTreeMap::new();
+    for (i, line) in code.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let parse_err = |message: &str| error::DotenvError::Parse {
+        path: path.display().to_string(),
+        line: i + 1,
+        message: message.to_string(),
+      };
+
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let (key, value) = line.split_once('=').ok_or_else(|| parse_err("expected KEY=value"))?;
+
+      let key = key.trim();
+      if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err(parse_err("invalid variable name"));
+      }
+
+      let value = parse_dotenv_value(value.trim()).map_err(parse_err)?;
+      vars.insert(key.to_string(), value);
     }
-  }
-}
 
-/// List of known configuration formats
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub enum Format {
-  #[cfg(feature = "json")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
-  Json,
-  #[cfg(feature = "jsonc")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "jsonc")))]
-  Jsonc,
-  #[cfg(feature = "toml")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
-  Toml,
-  #[cfg(feature = "yaml")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
-  Yaml,
+    Ok(Self { vars })
+  }
 }
 
-/// The configuration loader
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct ConfigLoader<T: Config> {
-  partial: T::Partial,
-}
+/// Parse the value of a `.env` line, with the surrounding whitespace already trimmed
+#[cfg(feature = "env")]
+fn parse_dotenv_value(value: &str) -> Result<String, &'static str> {
+  let mut chars = value.chars();
+  let quote = match chars.next() {
+    Some(quote @ ('"' | '\'')) => quote,
+    // unquoted values can have trailing comments
+    _ => {
+      return Ok(match value.find(" #") {
+        Some(i) => value[..i].trim_end().to_string(),
+        None => value.to_string(),
+      })
+    }
+  };
 
-impl<T: Config> ConfigLoader<T> {
-  /// Create a new configuration loader with all fields set as empty
-  pub fn new() -> Self {
-    Self {
-      partial: T::Partial::default(),
+  let mut target = String::new();
+  loop {
+    match chars.next() {
+      None => return Err("unterminated quoted value"),
+      Some(c) if c == quote => break,
+      Some('\\') if quote == '"' => match chars.next() {
+        Some('n') => target.push('\n'),
+        Some('r') => target.push('\r'),
+        Some('t') => target.push('\t'),
+        Some(c @ ('\\' | '"')) => target.push(c),
+        // unknown escapes are kept as is
+        Some(c) => {
+          target.push('\\');
+          target.push(c);
+        }
+        None => return Err("unterminated quoted value"),
+      },
+      Some(c) => target.push(c),
     }
   }
 
-  /// Add a partial configuration from a file
-  #[allow(clippy::result_large_err)]
-  pub fn file(&mut self, path: &str, format: Format) -> Result<&mut Self, Error> {
-    let code = std::fs::read_to_string(path).map_err(|e| Error::Io {
-      path: path.into(),
+  let rest = chars.as_str().trim_start();
+  if rest.is_empty() || rest.starts_with('#') {
+    Ok(target)
+  } else {
+    Err("unexpected characters after quoted value")
+  }
+}
+
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+impl EnvProvider for DotenvFileEnv {
+  type Error = Infallible;
+  fn get(&self, key: &str) -> Result<Option<String>, Self::Error> {
+    Ok(self.vars.get(key).cloned())
+  }
+
+  fn keys(&self) -> Option<Vec<String>> {
+    Some(self.vars.keys().cloned().collect())
+  }
+}
+
+/// An implementation of [`EnvProvider`] that reads from the first provider and falls back to the second one
+///
+/// Chains can be nested to combine more than two providers, eg: `ChainEnv(StdEnv, ChainEnv(dotenv, defaults))`
+///
+/// Errors from any of the providers are returned immediately
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+pub struct ChainEnv<A, B>(pub A, pub B);
+
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+impl<A: EnvProvider, B: EnvProvider> EnvProvider for ChainEnv<A, B> {
+  type Error = error::ChainEnvError<A::Error, B::Error>;
+  fn get(&self, key: &str) -> Result<Option<String>, Self::Error> {
+    match self.0.get(key).map_err(error::ChainEnvError::First)? {
+      Some(value) => Ok(Some(value)),
+      None => self.1.get(key).map_err(error::ChainEnvError::Second),
+    }
+  }
+
+  /// The union of the keys of both providers, or `None` if any of them cannot list its keys
+  fn keys(&self) -> Option<Vec<String>> {
+    let mut keys = self.0.keys()?;
+    for key in self.1.keys()? {
+      if !keys.contains(&key) {
+        keys.push(key);
+      }
+    }
+
+    Some(keys)
+  }
+}
+
+/// An implementation of [`EnvProvider`] that strips a prefix from the keys before reading them from the inner provider
+///
+/// Useful to read prefixed config keys from a provider with unprefixed keys,
+/// eg: with the `APP_` prefix the `APP_DB_PASSWORD` key is read as `DB_PASSWORD` from the inner provider
+///
+/// Keys that don't start with the prefix are not found, and [`EnvProvider::keys`] returns the keys of the inner provider with the prefix prepended
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+pub struct PrefixStripEnv<E> {
+  prefix: String,
+  inner: E,
+}
+
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+impl<E: EnvProvider> PrefixStripEnv<E> {
+  /// Create a new provider that strips `prefix` from the keys before reading them from `inner`
+  pub fn new(prefix: impl Into<String>, inner: E) -> Self {
+    Self {
+      prefix: prefix.into(),
+      inner,
+    }
+  }
+
+  /// The prefix that is stripped from the keys
+  pub fn prefix(&self) -> &str {
+    &self.prefix
+  }
+
+  /// Get the inner provider
+  pub fn into_inner(self) -> E {
+    self.inner
+  }
+}
+
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+impl<E: EnvProvider> EnvProvider for PrefixStripEnv<E> {
+  type Error = E::Error;
+  fn get(&self, key: &str) -> Result<Option<String>, Self::Error> {
+    match key.strip_prefix(self.prefix.as_str()) {
+      Some(key) => self.inner.get(key),
+      None => Ok(None),
+    }
+  }
+
+  fn keys(&self) -> Option<Vec<String>> {
+    let keys = self.inner.keys()?;
+    Some(keys.into_iter().map(|key| format!("{}{}", self.prefix, key)).collect())
+  }
+}
+
+/// Object safe version of [`EnvProvider`], used to pass providers of different types to [`ConfigLoader::env_with_providers`]
+///
+/// This trait is implemented for every [`EnvProvider`], the errors of the provider are converted to strings
+#[cfg(feature = "env")]
+#[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+pub trait DynEnvProvider {
+  /// See [`EnvProvider::get`]
+  fn get_dyn(&self, key: &str) -> Result<Option<String>, String>;
+
+  /// See [`EnvProvider::keys`]
+  fn keys_dyn(&self) -> Option<Vec<String>>;
+}
+
+#[cfg(feature = "env")]
+impl<E: EnvProvider> DynEnvProvider for E {
+  fn get_dyn(&self, key: &str) -> Result<Option<String>, String> {
+    self.get(key).map_err(|e| e.to_string())
+  }
+
+  fn keys_dyn(&self) -> Option<Vec<String>> {
+    self.keys()
+  }
+}
+
+#[cfg(feature = "env")]
+struct DynEnv<'a>(&'a dyn DynEnvProvider);
+
+#[cfg(feature = "env")]
+impl EnvProvider for DynEnv<'_> {
+  type Error = String;
+  fn get(&self, key: &str) -> Result<Option<String>, Self::Error> {
+    self.0.get_dyn(key)
+  }
+
+  fn keys(&self) -> Option<Vec<String>> {
+    self.0.keys_dyn()
+  }
+}
+
+/// A location from where a configuration was loaded
+///
+/// can be from Memory, File, or URL
+///
+/// The [`LoadLocation::Defaults`] and [`LoadLocation::Env`] locations are used to describe the stages added to a [`ConfigLoader`]
+///
+/// New locations can be added in the future, so matches on this enum need a wildcard arm
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum LoadLocation {
+  Memory,
+  /// The `#[config(default = value)]` attributes
+  Defaults,
+  /// Environment variables, with the optional prefix used to read them
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  Env(Option<String>),
+  /// A whole configuration document read from a single environment variable, see [`ConfigLoader::env_blob`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  EnvVar(String),
+  /// In-memory code with a human readable label, eg: "compiled defaults" or "remote override"
+  Named(String),
+  File(String),
+  Embedded(String),
+  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+  #[cfg_attr(docsrs, doc(cfg(any(feature = "url-blocking", feature = "url-async"))))]
+  Url(String),
+}
+
+impl LoadLocation {
+  /// Create a [`LoadLocation::Named`] location for in-memory code
+  pub fn named<S: Into<String>>(label: S) -> Self {
+    Self::Named(label.into())
+  }
+}
+
+impl Display for LoadLocation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use LoadLocation::*;
+    match self {
+      Memory => write!(f, "{}", "memory".yellow()),
+      Defaults => write!(f, "{}", "defaults".yellow()),
+      #[cfg(feature = "env")]
+      Env(None) => write!(f, "{}", "env".yellow()),
+      #[cfg(feature = "env")]
+      Env(Some(prefix)) => write!(f, "env with prefix: {}", prefix.yellow()),
+      #[cfg(feature = "env")]
+      EnvVar(key) => write!(f, "env var: {}", key.yellow()),
+      Named(label) => write!(f, "memory: {}", label.yellow()),
+      File(location) => write!(f, "file: {}", location.yellow()),
+      Embedded(location) => write!(f, "embedded file: {}", location.yellow()),
+      #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+      Url(location) => write!(f, "url: {}", location.yellow()),
+    }
+  }
+}
+
+/// Metadata of the response of a url stage, returned by [`ConfigLoader::url_with_meta`]
+///
+/// Useful to log or check that the remote config is not empty or truncated
+#[cfg(any(feature = "url-blocking", feature = "url-async"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "url-blocking", feature = "url-async"))))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct UrlMeta {
+  /// The status code of the response
+  pub status: u16,
+  /// The value of the `Content-Length` header of the response, if present
+  pub content_length: Option<u64>,
+  /// The length in bytes of the received body
+  pub body_length: usize,
+}
+
+/// A configuration file embedded in the binary at compile time
+///
+/// Usually created with the [`embed!`] macro and loaded with [`ConfigLoader::embedded`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Embedded {
+  /// The contents of the embedded file
+  pub code: &'static str,
+  /// The format of the embedded file
+  pub format: Format,
+  /// The path of the embedded file, used for error reporting
+  pub path: &'static str,
+}
+
+/// Embed a configuration file in the binary at compile time
+///
+/// The path is resolved like in [`include_str!`], relative to the file where the macro is invoked
+///
+/// usage:
+///
+/// ```text
+/// loader.embedded(metre::embed!("defaults.toml", Format::Toml))?;
+/// ```
+#[macro_export]
+macro_rules! embed {
+  ($path:literal, $format:expr) => {
+    $crate::Embedded {
+      code: ::core::include_str!($path),
+      format: $format,
+      path: $path,
+    }
+  };
+}
+
+/// List of known configuration formats
+///
+/// Formats are (de)serialized as lowercase strings, eg: `"json"` or `"toml"`
+///
+/// Numbers are loaded strictly in every format, a floating point value like `3000.5` or `3000.0` for an integer field is an error
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  #[cfg(feature = "json")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+  Json,
+  #[cfg(feature = "jsonc")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "jsonc")))]
+  Jsonc,
+  #[cfg(feature = "toml")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
+  Toml,
+  #[cfg(feature = "yaml")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
+  Yaml,
+}
+
+impl Format {
+  /// Try to detect the format of a configuration from its contents
+  ///
+  /// This is a best-effort heuristic: a leading `{` means JSON (or JSONC if it has comments),
+  /// a leading `---` or `key: value` line means YAML and a `key = value` or `[section]` line means TOML
+  ///
+  /// A first line that starts with `[` is only TOML if it is a table header like `[section]`, `[a.b]` or `[[items]]`,
+  /// other sequences like `[1, 2]` are JSON if they are valid JSON and YAML otherwise
+  ///
+  /// Returns `None` if the format is ambiguous or if the detected format is not enabled,
+  /// configuration files should prefer explicit formats
+  pub fn detect(code: &str) -> Option<Format> {
+    let code = code.trim_start();
+
+    if code.starts_with('{') {
+      if code.contains("//") || code.contains("/*") {
+        return Self::enabled("jsonc");
+      }
+
+      return Self::enabled("json").or_else(|| Self::enabled("jsonc"));
+    }
+
+    if code.starts_with("---") {
+      return Self::enabled("yaml");
+    }
+
+    let line = code
+      .lines()
+      .map(str::trim)
+      .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+
+    if line.starts_with('[') {
+      if is_toml_table_header(line) {
+        return Self::enabled("toml");
+      }
+
+      // a top level sequence, every JSON document is also a valid YAML document
+      #[cfg(feature = "json")]
+      if serde_json::from_str::<serde::de::IgnoredAny>(code).is_ok() {
+        return Self::enabled("json");
+      }
+
+      return Self::enabled("yaml");
+    }
+
+    match (line.find('='), line.find(':')) {
+      (Some(eq), Some(colon)) if eq < colon => Self::enabled("toml"),
+      (Some(_), None) => Self::enabled("toml"),
+      (_, Some(_)) => Self::enabled("yaml"),
+      (None, None) => None,
+    }
+  }
+
+  /// The file extensions of this format, without the leading dot, the first one is the canonical extension
+  ///
+  /// eg: `["yaml", "yml"]` for [`Format::Yaml`]
+  pub fn extensions(&self) -> &'static [&'static str] {
+    match *self {
+      #[cfg(feature = "json")]
+      Format::Json => &["json"],
+      #[cfg(feature = "jsonc")]
+      Format::Jsonc => &["jsonc"],
+      #[cfg(feature = "toml")]
+      Format::Toml => &["toml"],
+      #[cfg(feature = "yaml")]
+      Format::Yaml => &["yaml", "yml"],
+    }
+  }
+
+  /// Get the format from a `Content-Type` header value, eg: `application/json; charset=utf-8`
+  ///
+  /// Returns `None` if the content type is not recognized or if the format is not enabled
+  pub fn from_content_type(content_type: &str) -> Option<Format> {
+    Self::content_type_name(content_type).and_then(Self::enabled)
+  }
+
+  /// Get the name of the format of a `Content-Type` header value, even if the format is not enabled
+  fn content_type_name(content_type: &str) -> Option<&'static str> {
+    let essence = content_type
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .to_ascii_lowercase();
+
+    match essence.as_str() {
+      "application/json" | "text/json" => Some("json"),
+      "application/jsonc" => Some("jsonc"),
+      "application/toml" | "text/toml" | "text/x-toml" => Some("toml"),
+      "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Some("yaml"),
+      _ => None,
+    }
+  }
+
+  /// Get the canonical name of a format supported by metre, even if the format is not enabled
+  fn known_name(name: &str) -> Option<&'static str> {
+    match name {
+      "json" => Some("json"),
+      "jsonc" => Some("jsonc"),
+      "toml" => Some("toml"),
+      "yaml" | "yml" => Some("yaml"),
+      _ => None,
+    }
+  }
+
+  fn enabled(name: &str) -> Option<Format> {
+    match name {
+      #[cfg(feature = "json")]
+      "json" => Some(Format::Json),
+      #[cfg(feature = "jsonc")]
+      "jsonc" => Some(Format::Jsonc),
+      #[cfg(feature = "toml")]
+      "toml" => Some(Format::Toml),
+      #[cfg(feature = "yaml")]
+      "yaml" | "yml" => Some(Format::Yaml),
+      _ => None,
+    }
+  }
+
+  /// Returns true if the code has nothing but whitespace and comments
+  #[allow(unused)]
+  fn is_blank(&self, code: &str) -> bool {
+    match *self {
+      #[cfg(feature = "json")]
+      Format::Json => code.trim().is_empty(),
+
+      #[cfg(feature = "jsonc")]
+      Format::Jsonc => {
+        use std::io::Read;
+        let mut stripped = String::new();
+        match json_comments::StripComments::new(code.as_bytes()).read_to_string(&mut stripped) {
+          Ok(_) => stripped.trim().is_empty(),
+          // let the deserializer report the error
+          Err(_) => false,
+        }
+      }
+
+      #[cfg(feature = "toml")]
+      Format::Toml => is_blank_with_line_comments(code),
+
+      #[cfg(feature = "yaml")]
+      Format::Yaml => is_blank_with_line_comments(code),
+    }
+  }
+}
+
+/// Check if a line is a TOML table header with bare keys, eg: `[section]`, `[a.b]` or `[[items]]`, with an optional comment
+#[allow(unused)]
+fn is_toml_table_header(line: &str) -> bool {
+  let line = line.split('#').next().unwrap_or("").trim_end();
+  let keys = match line.strip_prefix("[[").and_then(|line| line.strip_suffix("]]")) {
+    Some(keys) => keys,
+    None => match line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+      Some(keys) => keys,
+      None => return false,
+    },
+  };
+
+  keys.split('.').all(|key| {
+    let key = key.trim();
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+  })
+}
+
+#[allow(unused)]
+fn is_blank_with_line_comments(code: &str) -> bool {
+  code.lines().all(|line| {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+  })
+}
+
+/// Parse a format from its lowercase name, eg: `"json"`, `"jsonc"`, `"toml"`, `"yaml"` or `"yml"`
+///
+/// This allows to load a [`Format`] from env variables or command line arguments
+impl std::str::FromStr for Format {
+  type Err = error::ParseFormatError;
+  fn from_str(name: &str) -> Result<Self, Self::Err> {
+    Format::enabled(&name.to_ascii_lowercase()).ok_or_else(|| error::ParseFormatError {
+      name: name.to_string(),
+    })
+  }
+}
+
+/// A stage added to a [`ConfigLoader`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Layer {
+  /// From where the stage was loaded
+  pub location: LoadLocation,
+  /// True if the stage didn't have any values
+  pub is_empty: bool,
+  /// The properties that had a value in the stage, see [`PartialConfig::list_present_properties`]
+  pub properties: Vec<String>,
+}
+
+/// A source of configuration to be added to a [`ConfigLoader`], see [`ConfigLoader::load`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Source {
+  /// See [`ConfigLoader::defaults`]
+  Defaults,
+  /// See [`ConfigLoader::file`]
+  File(PathBuf, Format),
+  /// See [`ConfigLoader::file_optional`]
+  FileOptional(PathBuf, Format),
+  /// See [`ConfigLoader::env`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  Env,
+  /// See [`ConfigLoader::env_with_prefix`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  EnvWithPrefix(String),
+  /// See [`ConfigLoader::code`]
+  Code(String, Format),
+}
+
+/// The configuration loader
+///
+/// The loader is [`Clone`] if the partial configuration is, see [`Self::finish_ref`]
+///
+/// With the `tracing` feature every stage is loaded inside a `config_stage` span, and a `debug!` event lists the
+/// `added` properties and the `overridden` ones that were already set by a previous stage (even if the value is the same),
+/// a `warn!` event is emitted for every field marked with `#[config(deprecated)]` that is set by the stage
+/// and for every field that is using its default value when the configuration is finished
+#[derive(Debug, Eq, PartialEq, Hash)]
+pub struct ConfigLoader<T: Config> {
+  partial: T::Partial,
+  layers: Vec<Layer>,
+  #[cfg(feature = "env")]
+  env_prefix_fmt: Option<String>,
+  #[cfg(feature = "env")]
+  env_vars: BTreeMap<String, String>,
+  #[cfg(feature = "env")]
+  verbose_missing: bool,
+}
+
+// derive(Clone) would also require T: Clone
+impl<T: Config> Clone for ConfigLoader<T>
+where
+  T::Partial: Clone,
+{
+  fn clone(&self) -> Self {
+    Self {
+      partial: self.partial.clone(),
+      layers: self.layers.clone(),
+      #[cfg(feature = "env")]
+      env_prefix_fmt: self.env_prefix_fmt.clone(),
+      #[cfg(feature = "env")]
+      env_vars: self.env_vars.clone(),
+      #[cfg(feature = "env")]
+      verbose_missing: self.verbose_missing,
+    }
+  }
+}
+
+impl<T: Config> ConfigLoader<T> {
+  /// Create a new configuration loader with all fields set as empty
+  pub fn new() -> Self {
+    Self {
+      partial: T::Partial::default(),
+      layers: vec![],
+      #[cfg(feature = "env")]
+      env_prefix_fmt: None,
+      #[cfg(feature = "env")]
+      env_vars: BTreeMap::new(),
+      #[cfg(feature = "env")]
+      verbose_missing: false,
+    }
+  }
+
+  /// Create a new configuration loader with the `#[config(default = value)]` attributes already applied
+  ///
+  /// This is the same as calling [`Self::new`] followed by [`Self::defaults`],
+  /// the defaults are the first stage so every stage added after will override them
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn with_defaults() -> Result<Self, Error> {
+    let mut loader = Self::new();
+    loader.defaults()?;
+    Ok(loader)
+  }
+
+  /// Create a new configuration loader with the state of an existing partial configuration
+  ///
+  /// This is useful to resume from a previously serialized partial state, see [`Self::partial_state`],
+  /// the partial configuration is used as is without merging it and it is recorded as the first stage of the loader
+  pub fn from_partial(partial: T::Partial) -> Self {
+    let mut loader = Self::new();
+    loader.layers.push(Layer {
+      location: LoadLocation::Memory,
+      is_empty: partial.is_empty(),
+      properties: partial.list_present_properties(),
+    });
+    loader.partial = partial;
+    loader
+  }
+
+  /// Load the final Config from the `#[config(default = value)]` attributes and the enviroment variables
+  ///
+  /// The defaults are applied before the env, so fields with a default value that are not set in the env will still be present in the final Config
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn from_env() -> Result<T, Error> {
+    let mut loader = Self::with_defaults()?;
+    loader.env()?;
+    loader.finish()
+  }
+
+  /// Same as [`Self::from_env`] but reading the enviroment variables with a prefix
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn from_env_with_prefix(prefix: &str) -> Result<T, Error> {
+    let mut loader = Self::with_defaults()?;
+    loader.env_with_prefix(prefix)?;
+    loader.finish()
+  }
+
+  /// Load the final Config from the `#[config(default = value)]` attributes and a file
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn from_file<P: AsRef<Path>>(path: P, format: Format) -> Result<T, Error> {
+    let mut loader = Self::with_defaults()?;
+    loader.file(path, format)?;
+    loader.finish()
+  }
+
+  /// Same as [`Self::from_file`] with the enviroment variables added after the file, so the env overrides the file values
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn from_file_with_env<P: AsRef<Path>>(path: P, format: Format) -> Result<T, Error> {
+    let mut loader = Self::with_defaults()?;
+    loader.file(path, format)?;
+    loader.env()?;
+    loader.finish()
+  }
+
+  /// Create a configuration from a list of sources, added in order
+  ///
+  /// This is a declarative alternative to the builder methods, eg:
+  ///
+  /// ```text
+  /// let config = ConfigLoader::<MyConfig>::load(&[
+  ///   Source::Defaults,
+  ///   Source::FileOptional("config.toml".into(), Format::Toml),
+  ///   Source::Env,
+  /// ])?;
+  /// ```
+  #[allow(clippy::result_large_err)]
+  pub fn load(sources: &[Source]) -> Result<T, Error> {
+    let mut loader = Self::new();
+    for source in sources {
+      loader.source(source)?;
+    }
+    loader.finish()
+  }
+
+  /// Add a partial configuration from a [`Source`]
+  #[allow(clippy::result_large_err)]
+  pub fn source(&mut self, source: &Source) -> Result<&mut Self, Error> {
+    match source {
+      Source::Defaults => self.defaults(),
+      Source::File(path, format) => self.file(path, *format),
+      Source::FileOptional(path, format) => self.file_optional(path, *format),
+      #[cfg(feature = "env")]
+      Source::Env => self.env(),
+      #[cfg(feature = "env")]
+      Source::EnvWithPrefix(prefix) => self.env_with_prefix(prefix),
+      Source::Code(code, format) => self.code(code, *format),
+    }
+  }
+
+  /// Add a partial configuration from a file
+  #[allow(clippy::result_large_err)]
+  pub fn file<P: AsRef<Path>>(&mut self, path: P, format: Format) -> Result<&mut Self, Error> {
+    let path = path.as_ref();
+    let code = std::fs::read_to_string(path).map_err(|e| Error::Io {
+      path: path.display().to_string(),
+      source: Arc::new(e),
+    })?;
+
+    self.code_with_location(&code, format, LoadLocation::File(path.display().to_string()))
+  }
+
+  /// Same as [`Self::file`] but only if `cond` is true, this is a no-op if `cond` is false
+  ///
+  /// eg: `loader.file("config.toml", Format::Toml)?.file_if(is_prod, "prod.toml", Format::Toml)?`
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn file_if<P: AsRef<Path>>(&mut self, cond: bool, path: P, format: Format) -> Result<&mut Self, Error> {
+    if cond {
+      self.file(path, format)
+    } else {
+      Ok(self)
+    }
+  }
+
+  /// Add a partial configuration from a file, if it exists
+  #[allow(clippy::result_large_err)]
+  pub fn file_optional<P: AsRef<Path>>(&mut self, path: P, format: Format) -> Result<&mut Self, Error> {
+    let path = path.as_ref();
+    let exists = path.try_exists().map_err(|e| Error::Io {
+      path: path.display().to_string(),
       source: Arc::new(e),
     })?;
 
-    self.code_with_location(&code, format, LoadLocation::File(path.to_string()))
+    if exists {
+      self.file(path, format)
+    } else {
+      Ok(self)
+    }
+  }
+
+  /// Add the `{base_dir}/default.{ext}` file and then the `{base_dir}/{profile}.{ext}` file if it exists,
+  /// where `ext` is the canonical extension of the format, see [`Format::extensions`]
+  ///
+  /// This is the usual layering of environment profiles like `dev`, `staging` or `prod`,
+  /// the profile name is often read from an env variable, eg: `loader.profile("config", &std::env::var("APP_PROFILE")?, Format::Toml)?`
+  #[allow(clippy::result_large_err)]
+  pub fn profile<P: AsRef<Path>>(&mut self, base_dir: P, profile: &str, format: Format) -> Result<&mut Self, Error> {
+    let base_dir = base_dir.as_ref();
+    let ext = format.extensions()[0];
+    self.file(base_dir.join(format!("default.{ext}")), format)?;
+    self.file_optional(base_dir.join(format!("{profile}.{ext}")), format)
+  }
+
+  /// Add a partial configuration from enviroment varialbes
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env(&mut self) -> Result<&mut Self, Error> {
+    self._env(&StdEnv, None)
+  }
+
+  /// Add a partial configuration from enviroment variables with a prefix
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env_with_prefix(&mut self, prefix: &str) -> Result<&mut Self, Error> {
+    self._env(&StdEnv, Some(prefix))
+  }
+
+  /// Add a partial configuration from enviroment variables with a custom provider
+  ///
+  /// The provider must implement the [`EnvProvider`] trait
+  ///
+  /// The [`EnvProvider`] trait is already implemented for several kinds of Maps from the standard library
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env_with_provider<E: EnvProvider>(&mut self, env: &E) -> Result<&mut Self, Error> {
+    self._env(env, None)
   }
 
-  /// Add a partial configuration from a file, if it exists
+  /// Add a partial configuration from each one of the providers, in order
+  ///
+  /// Every provider is added as a separate env stage, so the values of the later providers override the previous ones,
+  /// eg: `loader.env_with_providers(&[&defaults, &secrets], None)` where `secrets` has precedence over `defaults`
+  ///
+  /// See [`Self::env_with_provider`], use [`ChainEnv`] to combine providers in a single stage with the first one taking precedence
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
   #[allow(clippy::result_large_err)]
-  pub fn file_optional(&mut self, path: &str, format: Format) -> Result<&mut Self, Error> {
-    let exists = Path::new(path).try_exists().map_err(|e| Error::Io {
-      path: path.into(),
-      source: Arc::new(e),
+  pub fn env_with_providers(
+    &mut self,
+    providers: &[&dyn DynEnvProvider],
+    prefix: Option<&str>,
+  ) -> Result<&mut Self, Error> {
+    for provider in providers {
+      self._env(&DynEnv(*provider), prefix)?;
+    }
+
+    Ok(self)
+  }
+
+  /// See [`Self::env_with_provider`] and [`Self::env_with_prefix`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env_with_provider_and_prefix<E: EnvProvider>(
+    &mut self,
+    env: &E,
+    prefix: &str,
+  ) -> Result<&mut Self, Error> {
+    self._env(env, Some(prefix))
+  }
+
+  /// Same as [`Self::env_with_provider_and_prefix`] but with an optional prefix
+  ///
+  /// See [`PartialConfig::from_env_with_provider_and_optional_prefix`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env_with_provider_and_optional_prefix<E: EnvProvider>(
+    &mut self,
+    env: &E,
+    prefix: Option<&str>,
+  ) -> Result<&mut Self, Error> {
+    self._env(env, prefix)
+  }
+
+  /// Read the enviroment variables again and merge them over the current state, without reloading the other stages
+  ///
+  /// The prefix of the last env stage added to this loader is used, or no prefix if there are no env stages,
+  /// this is useful in hot reloads to pick up changed env variables without parsing the config files again
+  ///
+  /// Like any other stage, variables that are no longer set do not unset the values loaded before
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn refresh_env(&mut self) -> Result<&mut Self, Error> {
+    self.refresh_env_with_provider(&StdEnv)
+  }
+
+  /// Same as [`Self::refresh_env`] with a custom [`EnvProvider`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[allow(clippy::result_large_err)]
+  pub fn refresh_env_with_provider<E: EnvProvider>(&mut self, env: &E) -> Result<&mut Self, Error> {
+    let prefix = self.last_env_prefix();
+    self._env(env, prefix.as_deref())
+  }
+
+  /// Add a partial configuration from a whole document stored in a single enviroment variable
+  ///
+  /// eg: a `APP_CONFIG_YAML` variable with the full configuration in YAML format,
+  /// this is a no-op if the variable is not set
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn env_blob(&mut self, key: &str, format: Format) -> Result<&mut Self, Error> {
+    self.env_blob_with_provider(&StdEnv, key, format)
+  }
+
+  /// See [`Self::env_blob`] and [`Self::env_with_provider`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[allow(clippy::result_large_err)]
+  pub fn env_blob_with_provider<E: EnvProvider>(
+    &mut self,
+    env: &E,
+    key: &str,
+    format: Format,
+  ) -> Result<&mut Self, Error> {
+    let code = env.get(key).map_err(|e| FromEnvError {
+      key: key.to_string(),
+      field: String::new(),
+      message: e.to_string(),
+      source: None,
     })?;
 
-    if exists {
-      self.file(path, format)
+    match code {
+      None => Ok(self),
+      Some(code) => self._code(&code, format, LoadLocation::EnvVar(key.to_string())),
+    }
+  }
+
+  /// Set the value of an enviroment variable for this loader only
+  ///
+  /// The values are accumulated and added as an env stage when calling [`Self::apply_env`],
+  /// or before creating the final Config in [`Self::finish`] if there are values not yet applied
+  ///
+  /// This is primarily a testing convenience to avoid creating an [`EnvProvider`] map
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  pub fn set_env(&mut self, key: &str, value: &str) -> &mut Self {
+    self.env_vars.insert(key.to_string(), value.to_string());
+    self
+  }
+
+  /// Add a partial configuration from the enviroment variables set with [`Self::set_env`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn apply_env(&mut self) -> Result<&mut Self, Error> {
+    let env = std::mem::take(&mut self.env_vars);
+    self._env(&env, None)
+  }
+
+  /// Get the partial configuration that would be loaded from enviroment variables, without adding it to a loader
+  ///
+  /// This is useful to inspect the env contribution in isolation, eg: serializing the result to JSON
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn partial_from_env(prefix: Option<&str>) -> Result<T::Partial, Error> {
+    Self::partial_from_env_with_provider(&StdEnv, prefix)
+  }
+
+  /// Same as [`Self::partial_from_env`] with a custom [`EnvProvider`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn partial_from_env_with_provider<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+  ) -> Result<T::Partial, Error> {
+    let partial = T::Partial::from_env_with_provider_and_optional_prefix(env, prefix)?;
+    Ok(partial)
+  }
+
+  /// Check if the enviroment variables and the defaults are enough to build the config, without reading any other stage
+  ///
+  /// Returns a [`CheckEnvError::Missing`](error::CheckEnvError::Missing) with the list of required properties that are still missing,
+  /// this is useful as a pre-flight check in deployments
+  ///
+  /// Env variables that are set but cannot be parsed are reported as a [`CheckEnvError::FromEnv`](error::CheckEnvError::FromEnv) error
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  pub fn check_env(prefix: Option<&str>) -> Result<(), error::CheckEnvError> {
+    Self::check_env_with_provider(&StdEnv, prefix)
+  }
+
+  /// Same as [`Self::check_env`] with a custom [`EnvProvider`]
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  pub fn check_env_with_provider<E: EnvProvider>(
+    env: &E,
+    prefix: Option<&str>,
+  ) -> Result<(), error::CheckEnvError> {
+    let mut partial = T::Partial::defaults();
+    let env_partial = T::Partial::from_env_with_provider_and_optional_prefix(env, prefix)?;
+    partial.merge(env_partial)?;
+
+    let properties = partial.list_missing_properties();
+    if properties.is_empty() {
+      Ok(())
     } else {
-      Ok(self)
+      Err(error::CheckEnvError::Missing { properties })
     }
   }
 
-  /// Add a partial configuration from enviroment varialbes
+  /// Replace the `#[config(env_prefix)]` format of the config struct for the next env stages added to this loader
+  ///
+  /// Like in the attribute, the `{}` placeholder is filled with the prefix passed to [`Self::env_with_prefix`] (or an empty string for [`Self::env`]),
+  /// eg: `loader.env_prefix_override("{}OTHER_APP_").env_with_prefix("PROD_")` will read the `port` field from `PROD_OTHER_APP_PORT`
+  ///
+  /// Only the top level struct format is replaced, nested configurations keep using their own attributes
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  pub fn env_prefix_override(&mut self, env_prefix_fmt: &str) -> &mut Self {
+    self.env_prefix_fmt = Some(env_prefix_fmt.to_string());
+    self
+  }
+
+  /// List every environment variable key that would be read for this config with the given prefix,
+  /// including the nested configurations and without the `skip_env` fields
+  ///
+  /// See [`PartialConfig::env_keys_with_optional_prefix`], this is useful for ops tooling like a `--print-env-keys` command
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  #[inline(always)]
+  pub fn expected_env_keys(prefix: Option<&str>) -> Vec<String> {
+    T::Partial::env_keys_with_optional_prefix(prefix)
+  }
+
+  /// Generate a Markdown table with all the environment variables read by the config, with the field path,
+  /// if the variable is required and the field doc comment
+  ///
+  /// This is useful to document the deployment of an application, the table is always in sync with the config struct
+  #[cfg(feature = "env")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
+  pub fn env_docs() -> String {
+    let mut out = String::from("| Variable | Field | Required | Description |\n| --- | --- | --- | --- |\n");
+    for var in T::Partial::env_vars_with_optional_prefix(None) {
+      let doc = var.doc.replace('|', "\\|").replace('\n', " ");
+      let field = if var.field.is_empty() {
+        String::new()
+      } else {
+        format!("`{}`", var.field)
+      };
+      let required = if var.required { "yes" } else { "no" };
+      out.push_str(&format!("| `{}` | {} | {} | {} |\n", var.key, field, required, doc));
+    }
+
+    out
+  }
+
+  /// Include the environment variable that loads each missing property in the error returned by [`Self::finish`]
+  ///
+  /// With this option the missing properties error will be an [`Error::MissingPropertiesVerbose`] instead of an [`Error::FromPartial`],
+  /// the env keys are calculated with the prefix of the last env stage added to this loader
   #[cfg(feature = "env")]
   #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
   #[inline(always)]
+  pub fn verbose_missing(&mut self) -> &mut Self {
+    self.verbose_missing = true;
+    self
+  }
+
+  /// Add a partial configuration from in-memory code
+  #[inline(always)]
   #[allow(clippy::result_large_err)]
-  pub fn env(&mut self) -> Result<&mut Self, Error> {
-    self._env(&StdEnv, None)
+  pub fn code<S: AsRef<str>>(&mut self, code: S, format: Format) -> Result<&mut Self, Error> {
+    self._code(code.as_ref(), format, LoadLocation::Memory)
+  }
+
+  /// Same as [`Self::code`] but only if `cond` is true, this is a no-op if `cond` is false
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn code_if<S: AsRef<str>>(&mut self, cond: bool, code: S, format: Format) -> Result<&mut Self, Error> {
+    if cond {
+      self.code(code, format)
+    } else {
+      Ok(self)
+    }
+  }
+
+  /// Add a partial configuration from in-memory code with the format given by name, eg: `"toml"`
+  ///
+  /// See [`Format::from_str`](std::str::FromStr::from_str) for the accepted names, this will error if the name is unknown,
+  /// or with an [`Error::UnsupportedFormat`] if the format feature is not enabled
+  #[allow(clippy::result_large_err)]
+  pub fn code_str<S: AsRef<str>>(&mut self, code: S, format_name: &str) -> Result<&mut Self, Error> {
+    let format: Format = match format_name.parse() {
+      Ok(format) => format,
+      Err(e) => match Format::known_name(&format_name.to_ascii_lowercase()) {
+        Some(name) => {
+          return Err(Error::UnsupportedFormat {
+            format: name.to_string(),
+          })
+        }
+        None => return Err(e.into()),
+      },
+    };
+
+    self._code(code.as_ref(), format, LoadLocation::Memory)
+  }
+
+  /// Add a partial configuration from in-memory code of unknown format
+  ///
+  /// The format is detected with [`Format::detect`], this will error if the format cannot be detected
+  #[allow(clippy::result_large_err)]
+  pub fn code_detect<S: AsRef<str>>(&mut self, code: S) -> Result<&mut Self, Error> {
+    let code = code.as_ref();
+    match Format::detect(code) {
+      Some(format) => self._code(code, format, LoadLocation::Memory),
+      None => Err(Error::UnknownFormat {
+        location: LoadLocation::Memory,
+      }),
+    }
+  }
+
+  /// Add a partial configuration from in-memory code
+  ///
+  /// Specifying the [`LoadLocation`] of the in-memory code is useful for error reporting
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn code_with_location<S: AsRef<str>>(
+    &mut self,
+    code: S,
+    format: Format,
+    location: LoadLocation,
+  ) -> Result<&mut Self, Error> {
+    self._code(code.as_ref(), format, location)
+  }
+
+  /// Same as [`Self::code_with_location`] with a [`LoadLocation::Named`] location, eg: `loader.code_labeled(code, Format::Toml, "cli overrides")`
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn code_labeled<S: AsRef<str>>(&mut self, code: S, format: Format, label: &str) -> Result<&mut Self, Error> {
+    self._code(code.as_ref(), format, LoadLocation::named(label))
   }
 
-  /// Add a partial configuration from enviroment variables with a prefix
-  #[cfg(feature = "env")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
-  #[inline(always)]
-  #[allow(clippy::result_large_err)]
-  pub fn env_with_prefix(&mut self, prefix: &str) -> Result<&mut Self, Error> {
-    self._env(&StdEnv, Some(prefix))
+  /// Add a partial configuration from an already parsed [`serde_json::Value`]
+  ///
+  /// This avoids serializing the value to a string just to parse it again with [`ConfigLoader::code`]
+  #[cfg(feature = "json")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+  #[allow(clippy::result_large_err)]
+  pub fn value(&mut self, value: serde_json::Value) -> Result<&mut Self, Error> {
+    let partial: T::Partial = serde_path_to_error::deserialize(value).map_err(|e| {
+      let path = e.path().to_string();
+      Error::from_json(e.into_inner(), LoadLocation::Memory).at_field(path, &LoadLocation::Memory)
+    })?;
+
+    self._add(partial, LoadLocation::Memory)
+  }
+
+  /// Add a partial configuration from the standard input
+  ///
+  /// The location is reported as `LoadLocation::Named("stdin")`, an empty stdin adds an empty stage instead of failing
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn stdin(&mut self, format: Format) -> Result<&mut Self, Error> {
+    self.reader(std::io::stdin(), format, "stdin")
+  }
+
+  /// Add a partial configuration from any [`std::io::Read`] implementation, eg: a socket or a child process output
+  ///
+  /// The reader is consumed until the end, the location and I/O errors are reported with the given label,
+  /// an empty input adds an empty stage instead of failing
+  #[allow(clippy::result_large_err)]
+  pub fn reader<R: std::io::Read>(&mut self, mut reader: R, format: Format, label: &str) -> Result<&mut Self, Error> {
+    let mut code = String::new();
+    reader
+      .read_to_string(&mut code)
+      .map_err(|e| Error::from_io(e, label))?;
+
+    self._code(&code, format, LoadLocation::named(label))
+  }
+
+  /// Add a partial configuration from a file embedded at compile time with the [`embed!`] macro
+  ///
+  /// Errors will report the path of the embedded file instead of a bare memory location
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn embedded(&mut self, embedded: Embedded) -> Result<&mut Self, Error> {
+    self._code(
+      embedded.code,
+      embedded.format,
+      LoadLocation::Embedded(embedded.path.to_string()),
+    )
+  }
+
+  /// Add a partial configuration from a url
+  #[cfg(feature = "url-blocking")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-blocking")))]
+  #[allow(clippy::result_large_err)]
+  pub fn url(&mut self, url: &str, format: Format) -> Result<&mut Self, Error> {
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
+
+    let code = reqwest::blocking::get(url)
+      .map_err(map_err)?
+      .text()
+      .map_err(map_err)?;
+
+    self._code(&code, format, LoadLocation::Url(url.to_string()))
+  }
+
+  /// Same as [`Self::url`] but also returns the [`UrlMeta`] of the response
+  #[cfg(feature = "url-blocking")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-blocking")))]
+  #[allow(clippy::result_large_err)]
+  pub fn url_with_meta(&mut self, url: &str, format: Format) -> Result<(&mut Self, UrlMeta), Error> {
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
+
+    let res = reqwest::blocking::get(url).map_err(map_err)?;
+    let status = res.status().as_u16();
+    let content_length = res.content_length();
+    let code = res.text().map_err(map_err)?;
+
+    let meta = UrlMeta {
+      status,
+      content_length,
+      body_length: code.len(),
+    };
+
+    let me = self._code(&code, format, LoadLocation::Url(url.to_string()))?;
+    Ok((me, meta))
+  }
+
+  /// Same as [`Self::url_with_meta`] but async
+  #[cfg(feature = "url-async")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-async")))]
+  pub async fn url_with_meta_async(&mut self, url: &str, format: Format) -> Result<(&mut Self, UrlMeta), Error> {
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
+
+    let res = reqwest::get(url).await.map_err(map_err)?;
+    let status = res.status().as_u16();
+    let content_length = res.content_length();
+    let code = res.text().await.map_err(map_err)?;
+
+    let meta = UrlMeta {
+      status,
+      content_length,
+      body_length: code.len(),
+    };
+
+    let me = self._code(&code, format, LoadLocation::Url(url.to_string()))?;
+    Ok((me, meta))
   }
 
-  /// Add a partial configuration from enviroment variables with a custom provider
+  /// Add a partial configuration from a url, the format is taken from the `Content-Type` header of the response
   ///
-  /// The provider must implement the [`EnvProvider`] trait
-  ///
-  /// The [`EnvProvider`] trait is already implemented for several kinds of Maps from the standard library
-  #[cfg(feature = "env")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
-  #[inline(always)]
+  /// See [`Format::from_content_type`], this will error if the content type is not recognized
+  #[cfg(feature = "url-blocking")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-blocking")))]
   #[allow(clippy::result_large_err)]
-  pub fn env_with_provider<E: EnvProvider>(&mut self, env: &E) -> Result<&mut Self, Error> {
-    self._env(env, None)
-  }
+  pub fn url_auto(&mut self, url: &str) -> Result<&mut Self, Error> {
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
 
-  /// See [`Self::env_with_provider`] and [`Self::env_with_prefix`]
-  #[cfg(feature = "env")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "env")))]
-  #[inline(always)]
-  #[allow(clippy::result_large_err)]
-  pub fn env_with_provider_and_prefix<E: EnvProvider>(
-    &mut self,
-    env: &E,
-    prefix: &str,
-  ) -> Result<&mut Self, Error> {
-    self._env(env, Some(prefix))
+    let res = reqwest::blocking::get(url).map_err(map_err)?;
+    let format = Self::_content_type_format(url, res.headers())?;
+    let code = res.text().map_err(map_err)?;
+
+    self._code(&code, format, LoadLocation::Url(url.to_string()))
   }
 
-  /// Add a partial configuration from in-memory code
-  #[inline(always)]
-  #[allow(clippy::result_large_err)]
-  pub fn code<S: AsRef<str>>(&mut self, code: S, format: Format) -> Result<&mut Self, Error> {
-    self._code(code.as_ref(), format, LoadLocation::Memory)
+  /// Add a partial configuration from a url, the format is taken from the `Content-Type` header of the response, async version
+  #[cfg(feature = "url-async")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-async")))]
+  pub async fn url_auto_async(&mut self, url: &str) -> Result<&mut Self, Error> {
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
+
+    let res = reqwest::get(url).await.map_err(map_err)?;
+    let format = Self::_content_type_format(url, res.headers())?;
+    let code = res.text().await.map_err(map_err)?;
+
+    self._code(&code, format, LoadLocation::Url(url.to_string()))
   }
 
-  /// Add a partial configuration from in-memory code
+  /// Same as [`Self::url`] but retrying the request on connection errors, timeouts and 5xx responses
   ///
-  /// Specifying the [`LoadLocation`] of the in-memory code is useful for error reporting
-  #[inline(always)]
+  /// The request is retried up to `retries` times, waiting `backoff` before the first retry and doubling the wait on each attempt,
+  /// unlike [`Self::url`] any non successful response status is an error
+  #[cfg(feature = "url-blocking")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-blocking")))]
   #[allow(clippy::result_large_err)]
-  pub fn code_with_location<S: AsRef<str>>(
+  pub fn url_with_retry(
     &mut self,
-    code: S,
+    url: &str,
     format: Format,
-    location: LoadLocation,
+    retries: u32,
+    backoff: Duration,
   ) -> Result<&mut Self, Error> {
-    self._code(code.as_ref(), format, location)
+    let map_err = |e| Error::Network {
+      url: url.to_string(),
+      source: Arc::new(e),
+    };
+
+    let mut attempt = 0;
+    let code = loop {
+      let result = reqwest::blocking::get(url)
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.text());
+
+      match result {
+        Ok(code) => break code,
+        Err(e) => {
+          if attempt >= retries || !Self::_is_retryable(&e) {
+            return Err(map_err(e));
+          }
+          std::thread::sleep(Self::_retry_delay(backoff, attempt));
+          attempt += 1;
+        }
+      }
+    };
+
+    self._code(&code, format, LoadLocation::Url(url.to_string()))
   }
 
-  /// Add a partial configuration from a url
-  #[cfg(feature = "url-blocking")]
-  #[cfg_attr(docsrs, doc(cfg(feature = "url-blocking")))]
-  #[allow(clippy::result_large_err)]
-  pub fn url(&mut self, url: &str, format: Format) -> Result<&mut Self, Error> {
+  /// Same as [`Self::url_with_retry`] but async
+  #[cfg(feature = "url-async")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "url-async")))]
+  pub async fn url_with_retry_async(
+    &mut self,
+    url: &str,
+    format: Format,
+    retries: u32,
+    backoff: Duration,
+  ) -> Result<&mut Self, Error> {
     let map_err = |e| Error::Network {
       url: url.to_string(),
       source: Arc::new(e),
     };
 
-    let code = reqwest::blocking::get(url)
-      .map_err(map_err)?
-      .text()
-      .map_err(map_err)?;
+    let mut attempt = 0;
+    let code = loop {
+      let result = match reqwest::get(url).await.and_then(|res| res.error_for_status()) {
+        Ok(res) => res.text().await,
+        Err(e) => Err(e),
+      };
+
+      match result {
+        Ok(code) => break code,
+        Err(e) => {
+          if attempt >= retries || !Self::_is_retryable(&e) {
+            return Err(map_err(e));
+          }
+          tokio::time::sleep(Self::_retry_delay(backoff, attempt)).await;
+          attempt += 1;
+        }
+      }
+    };
 
     self._code(&code, format, LoadLocation::Url(url.to_string()))
   }
 
+  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+  fn _is_retryable(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().is_some_and(|status| status.is_server_error())
+  }
+
+  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+  fn _retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff.saturating_mul(2u32.saturating_pow(attempt))
+  }
+
+  #[cfg(any(feature = "url-blocking", feature = "url-async"))]
+  #[allow(clippy::result_large_err)]
+  fn _content_type_format(url: &str, headers: &reqwest::header::HeaderMap) -> Result<Format, Error> {
+    let name = headers
+      .get(reqwest::header::CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .and_then(Format::content_type_name)
+      .ok_or_else(|| Error::UnknownFormat {
+        location: LoadLocation::Url(url.to_string()),
+      })?;
+
+    Format::enabled(name).ok_or_else(|| Error::UnsupportedFormat {
+      format: name.to_string(),
+    })
+  }
+
   #[cfg(feature = "url-async")]
   #[cfg_attr(docsrs, doc(cfg(feature = "url-async")))]
   /// Add a partial configuration from a url, async version
@@ -523,8 +2150,12 @@ impl<T: Config> ConfigLoader<T> {
   #[inline(always)]
   #[allow(clippy::result_large_err)]
   fn _env<E: EnvProvider>(&mut self, env: &E, prefix: Option<&str>) -> Result<&mut Self, Error> {
-    let partial = T::Partial::from_env_with_provider_and_optional_prefix(env, prefix)?;
-    self._add(partial)
+    let partial = T::Partial::from_env_with_provider_and_env_prefix_fmt(
+      env,
+      prefix,
+      self.env_prefix_fmt.as_deref(),
+    )?;
+    self._add(partial, LoadLocation::Env(prefix.map(String::from)))
   }
 
   #[allow(unused)]
@@ -535,60 +2166,262 @@ impl<T: Config> ConfigLoader<T> {
     format: Format,
     location: LoadLocation,
   ) -> Result<&mut Self, Error> {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(location = %location, format = ?format, "parsing config stage");
+
+    // empty or comment only sources are added as empty stages
+    if format.is_blank(code) {
+      return self._add(T::Partial::default(), location);
+    }
+
     let partial = match format {
       #[cfg(feature = "json")]
       #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
-      Format::Json => serde_json::from_str(code).map_err(|e| Error::Json {
-        location,
-        source: Arc::new(e),
-      })?,
+      Format::Json => {
+        let mut de = serde_json::Deserializer::from_str(code);
+        let partial: T::Partial =
+          serde_path_to_error::deserialize(&mut de).map_err(|e| {
+            let path = e.path().to_string();
+            Error::from_json(e.into_inner(), location.clone()).at_field(path, &location)
+          })?;
+
+        // check that there are no trailing characters like serde_json::from_str does
+        de.end().map_err(|e| Error::Json {
+          location: location.clone(),
+          source: Arc::new(e),
+        })?;
+
+        partial
+      }
 
       #[cfg(feature = "jsonc")]
       #[cfg_attr(docsrs, doc(cfg(feature = "jsonc")))]
       Format::Jsonc => {
         let reader = json_comments::StripComments::new(code.as_bytes());
-        serde_json::from_reader(reader).map_err(|e| Error::Json {
-          location,
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let partial: T::Partial =
+          serde_path_to_error::deserialize(&mut de).map_err(|e| {
+            let path = e.path().to_string();
+            Error::from_json(e.into_inner(), location.clone()).at_field(path, &location)
+          })?;
+
+        // check that there are no trailing characters like serde_json::from_str does
+        de.end().map_err(|e| Error::Json {
+          location: location.clone(),
           source: Arc::new(e),
-        })?
+        })?;
+
+        partial
       }
 
       #[cfg(feature = "toml")]
       #[cfg_attr(docsrs, doc(cfg(feature = "toml")))]
-      Format::Toml => toml::from_str(code).map_err(|e| Error::Toml {
-        location,
-        source: e,
-      })?,
+      Format::Toml => {
+        serde_path_to_error::deserialize(toml::Deserializer::new(code)).map_err(|e| {
+          let path = e.path().to_string();
+          Error::from_toml(e.into_inner(), location.clone()).at_field(path, &location)
+        })?
+      }
 
       #[cfg(feature = "yaml")]
       #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
-      Format::Yaml => serde_yaml::from_str(code).map_err(|e| Error::Yaml {
-        location,
-        source: Arc::new(e),
-      })?,
+      Format::Yaml => {
+        serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(code)).map_err(|e| {
+          let path = e.path().to_string();
+          Error::from_yaml(e.into_inner(), location.clone()).at_field(path, &location)
+        })?
+      }
     };
 
-    self._add(partial)
+    self._add(partial, location)
   }
 
   /// Add a partial configuration from the `#[config(default = value)]` attributes
   #[inline(always)]
   #[allow(clippy::result_large_err)]
   pub fn defaults(&mut self) -> Result<&mut Self, Error> {
-    self._add(T::Partial::defaults())
+    self._add(T::Partial::defaults(), LoadLocation::Defaults)
   }
 
   /// Add a pre generated partial configuration
   #[inline(always)]
   #[allow(clippy::result_large_err)]
   pub fn partial(&mut self, partial: T::Partial) -> Result<&mut Self, Error> {
-    self._add(partial)
+    self._add(partial, LoadLocation::Memory)
+  }
+
+  /// Add each one of the pre generated partial configurations, in order
+  ///
+  /// See [`Self::partial`], every partial is added as a separate stage
+  #[allow(clippy::result_large_err)]
+  pub fn extend<I: IntoIterator<Item = T::Partial>>(&mut self, partials: I) -> Result<&mut Self, Error> {
+    for partial in partials {
+      self.partial(partial)?;
+    }
+
+    Ok(self)
   }
 
   #[inline(always)]
   #[allow(clippy::result_large_err)]
-  fn _add(&mut self, partial: T::Partial) -> Result<&mut Self, Error> {
+  fn _add(&mut self, partial: T::Partial, location: LoadLocation) -> Result<&mut Self, Error> {
+    let is_empty = partial.is_empty();
+    let properties = partial.list_present_properties();
+
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("config_stage", location = %location).entered();
+
+    #[cfg(feature = "tracing")]
+    {
+      // the properties set by this stage, split by whether a previous stage already set them
+      let previous = self.partial.list_present_properties();
+      let (overridden, added): (Vec<&String>, Vec<&String>) =
+        properties.iter().partition(|prop| previous.contains(prop));
+
+      tracing::debug!(
+        location = %location,
+        empty = is_empty,
+        added = ?added,
+        overridden = ?overridden,
+        "config stage loaded"
+      );
+
+      for (field, note) in partial.list_deprecated_properties() {
+        tracing::warn!(location = %location, field = %field, note = %note, "deprecated config field is set");
+      }
+    }
+
     self.partial.merge(partial)?;
+    self.layers.push(Layer {
+      location,
+      is_empty,
+      properties,
+    });
+    Ok(self)
+  }
+
+  /// Get the list of stages added to this loader, in the order they were added
+  #[inline(always)]
+  pub fn layers(&self) -> &[Layer] {
+    &self.layers
+  }
+
+  /// Get the number of stages added to this loader, including empty ones
+  #[inline(always)]
+  pub fn layers_applied(&self) -> usize {
+    self.layers.len()
+  }
+
+  /// Get the locations of the stages added to this loader, in the order they were added
+  pub fn layer_locations(&self) -> Vec<&LoadLocation> {
+    self.layers.iter().map(|layer| &layer.location).collect()
+  }
+
+  /// Get the list of properties whose value comes from a [`Self::defaults`] stage
+  /// and were not overriden by any other stage added after it
+  ///
+  /// This is useful to detect forgotten deployment settings, it doesn't affect [`Self::finish`]
+  pub fn defaulted_fields(&self) -> Vec<String> {
+    let mut defaulted = vec![];
+    for (i, layer) in self.layers.iter().enumerate() {
+      if layer.location != LoadLocation::Defaults {
+        continue;
+      }
+
+      for prop in &layer.properties {
+        let overriden = self.layers[i + 1..]
+          .iter()
+          .any(|next| next.location != LoadLocation::Defaults && next.properties.contains(prop));
+
+        if !overriden && !defaulted.contains(prop) {
+          defaulted.push(prop.clone());
+        }
+      }
+    }
+
+    defaulted
+  }
+
+  /// Get a human readable summary of the stages added to this loader
+  /// and the properties that are still missing in the partial configuration
+  ///
+  /// This is useful for debugging why a value was not loaded, it can be called at any time and never fails
+  pub fn explain(&self) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+
+    if self.layers.is_empty() {
+      let _ = writeln!(out, "no stages added");
+    } else {
+      let _ = writeln!(out, "stages (from lowest to highest priority):");
+      for (i, layer) in self.layers.iter().enumerate() {
+        if layer.is_empty {
+          let _ = writeln!(out, "  {}. {} {}", i + 1, layer.location, "(empty)".dimmed());
+        } else {
+          let _ = writeln!(out, "  {}. {}", i + 1, layer.location);
+        }
+      }
+    }
+
+    let missing_properties = self.partial.list_missing_properties();
+    if missing_properties.is_empty() {
+      let _ = write!(out, "no missing properties");
+    } else {
+      let _ = write!(
+        out,
+        "missing properties: {}",
+        missing_properties
+          .iter()
+          .map(|name| name.yellow().to_string())
+          .collect::<Vec<_>>()
+          .join(", ")
+      );
+    }
+
+    out
+  }
+
+  /// List the properties that differ between the current partial configuration and `other`
+  ///
+  /// This is useful to log what changed when reloading a configuration, eg: `["log.level", "timeout"]`
+  ///
+  /// The partial configurations are compared through their [`serde_json::Value`] serialization, because the generated
+  /// partial structs don't implement [`PartialEq`] for every field type, this is why this method requires the `json` feature
+  ///
+  /// Lists are compared as a whole, if any of the partial configurations fails to serialize an [`Error::Json`] is returned
+  #[cfg(feature = "json")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+  #[allow(clippy::result_large_err)]
+  pub fn diff(&self, other: &T::Partial) -> Result<Vec<String>, Error>
+  where
+    T::Partial: serde::Serialize,
+  {
+    let map_err = |e| Error::from_json(e, LoadLocation::Memory);
+    let left = serde_json::to_value(&self.partial).map_err(map_err)?;
+    let right = serde_json::to_value(other).map_err(map_err)?;
+
+    let mut changed = vec![];
+    diff_values("", &left, &right, &mut changed);
+    Ok(changed)
+  }
+
+  /// Modify the accumulated partial configuration with a closure
+  ///
+  /// Unlike [`Self::partial_state_mut`] this can be chained with other stages and propagates the closure error,
+  /// eg: `loader.file(path, format)?.apply(|partial| inject_computed(partial))?.env()?`
+  ///
+  /// if the closure fails an [`Error::Apply`] is returned with the original error as the source
+  #[allow(clippy::result_large_err)]
+  pub fn apply<E, F>(&mut self, f: F) -> Result<&mut Self, Error>
+  where
+    E: std::error::Error + Send + Sync + 'static,
+    F: FnOnce(&mut T::Partial) -> Result<(), E>,
+  {
+    f(&mut self.partial).map_err(|e| Error::Apply {
+      message: e.to_string(),
+      source: Arc::new(e),
+    })?;
     Ok(self)
   }
 
@@ -606,15 +2439,263 @@ impl<T: Config> ConfigLoader<T> {
     &mut self.partial
   }
 
+  /// Consume the loader and get the partial configuration with the sum of all previously added stages
+  ///
+  /// Unlike [`Self::finish`] the values set with [`Self::set_env`] are not applied, call [`Self::apply_env`] before this function to include them
+  #[inline(always)]
+  pub fn take_partial(self) -> T::Partial {
+    self.partial
+  }
+
   /// Get the final Config from the sum of all previously added stages
   ///
   /// this function will error if there are missing required properties
   #[inline(always)]
   #[allow(clippy::result_large_err)]
-  pub fn finish(self) -> Result<T, Error> {
+  pub fn finish(#[allow(unused_mut)] mut self) -> Result<T, Error> {
+    #[cfg(feature = "env")]
+    if !self.env_vars.is_empty() {
+      self.apply_env()?;
+    }
+
+    #[cfg(feature = "tracing")]
+    for field in self.defaulted_fields() {
+      tracing::warn!(field = %field, "config field is using its default value");
+    }
+
+    #[cfg(feature = "env")]
+    if self.verbose_missing {
+      let prefix = self.last_env_prefix();
+      return T::from_partial(self.partial).map_err(|e| {
+        let env_keys = e
+          .missing_properties
+          .iter()
+          .map(|prop| T::Partial::property_env_key(prop, prefix.as_deref()))
+          .collect();
+        Error::MissingPropertiesVerbose { source: e, env_keys }
+      });
+    }
+
     let v = T::from_partial(self.partial)?;
     Ok(v)
   }
+
+  /// Same as [`Self::finish`] but on failure returns a [`Report`](error::Report) that renders a multi-line diagnostic with [`miette`]
+  ///
+  /// the report lists every missing property with its env key and a suggestion to fix it
+  #[cfg(feature = "miette")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "miette")))]
+  #[allow(clippy::result_large_err)]
+  pub fn finish_or_report(self) -> Result<T, error::Report> {
+    #[cfg(feature = "env")]
+    let prefix = self.last_env_prefix();
+
+    self.finish().map_err(|error| {
+      let missing = error
+        .missing_properties()
+        .unwrap_or_default()
+        .iter()
+        .map(|property| error::MissingProperty {
+          property: property.clone(),
+          #[cfg(feature = "env")]
+          env_key: T::Partial::property_env_key(property, prefix.as_deref()),
+          #[cfg(not(feature = "env"))]
+          env_key: None,
+        })
+        .collect();
+
+      error::Report { error, missing }
+    })
+  }
+
+  /// The prefix of the last env stage, used to calculate the env keys of the missing properties
+  #[cfg(feature = "env")]
+  fn last_env_prefix(&self) -> Option<String> {
+    self
+      .layers
+      .iter()
+      .rev()
+      .find_map(|layer| match &layer.location {
+        LoadLocation::Env(prefix) => Some(prefix.clone()),
+        _ => None,
+      })
+      .flatten()
+  }
+
+  /// Same as [`Self::finish`] but without consuming the loader, so more stages can be added and the config can be built again
+  ///
+  /// This requires the partial configuration to implement [`Clone`], use `#[config(partial_derive(Clone))]` in the config struct
+  /// and in all the nested configurations to derive it for the generated PartialConfig structs
+  #[allow(clippy::result_large_err)]
+  pub fn finish_ref(&self) -> Result<T, Error>
+  where
+    T::Partial: Clone,
+  {
+    self.clone().finish()
+  }
+
+  /// Same as [`Self::finish`] but returns the final Config in an [`Arc`]
+  ///
+  /// This is the recommended way to finish the loader in multi-threaded services where the config is shared between tasks
+  #[inline(always)]
+  #[allow(clippy::result_large_err)]
+  pub fn finish_arc(self) -> Result<Arc<T>, Error> {
+    let v = self.finish()?;
+    Ok(Arc::new(v))
+  }
+
+  /// Same as [`Self::finish`] but runs a validation function over the final Config
+  ///
+  /// Use this to enforce invariants between fields, eg: `min < max`,
+  /// if the validation fails an [`Error::Validation`] is returned with the error message
+  #[allow(clippy::result_large_err)]
+  pub fn finish_validated<E, F>(self, validate: F) -> Result<T, Error>
+  where
+    E: Display,
+    F: FnOnce(&T) -> Result<(), E>,
+  {
+    let v = self.finish()?;
+    validate(&v).map_err(|e| Error::Validation {
+      message: e.to_string(),
+    })?;
+    Ok(v)
+  }
+
+  /// Same as [`Self::finish`] but the missing required properties are filled from [`Default::default`]
+  ///
+  /// The default value is converted to JSON through its [`serde::Serialize`] implementation,
+  /// so the serialized field names of `T` must match the ones of the Partial struct,
+  /// only the properties reported by [`PartialConfig::list_missing_properties`] are taken from it,
+  /// they are assigned directly without calling the `merge` functions of the fields and are recorded as a [`LoadLocation::Defaults`] stage,
+  /// the config is then built with [`Self::finish`]
+  ///
+  /// Note that this weakens the guarantee that every required property was explicitly configured,
+  /// prefer `#[config(default = value)]` attributes and [`Self::finish`] when possible
+  #[cfg(feature = "json")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+  #[allow(clippy::result_large_err)]
+  pub fn finish_or_default(#[allow(unused_mut)] mut self) -> Result<T, Error>
+  where
+    T: Default + serde::Serialize,
+    T::Partial: serde::Serialize,
+  {
+    #[cfg(feature = "env")]
+    if !self.env_vars.is_empty() {
+      self.apply_env()?;
+    }
+
+    let missing = self.partial.list_missing_properties();
+    if !missing.is_empty() {
+      let map_err = |e| Error::Json {
+        location: LoadLocation::Defaults,
+        source: Arc::new(e),
+      };
+
+      let defaults = serde_json::to_value(T::default()).map_err(map_err)?;
+      let mut value = serde_json::to_value(&self.partial).map_err(map_err)?;
+
+      let mut properties = vec![];
+      for prop in missing {
+        let segments = property_segments(&prop);
+        if let Some(default) = json_get(&defaults, &segments) {
+          if json_set(&mut value, &segments, default.clone()) {
+            properties.push(prop);
+          }
+        }
+      }
+
+      self.partial = serde_json::from_value(value).map_err(map_err)?;
+
+      // the default values are below every other stage
+      self.layers.insert(
+        0,
+        Layer {
+          location: LoadLocation::Defaults,
+          is_empty: properties.is_empty(),
+          properties,
+        },
+      );
+    }
+
+    self.finish()
+  }
+}
+
+/// Split a property path like `servers[0].port` in its keys and indexes
+#[cfg(feature = "json")]
+fn property_segments(path: &str) -> Vec<&str> {
+  path
+    .split(['.', '[', ']'])
+    .filter(|segment| !segment.is_empty())
+    .collect()
+}
+
+#[cfg(feature = "json")]
+fn json_get<'a>(value: &'a serde_json::Value, segments: &[&str]) -> Option<&'a serde_json::Value> {
+  segments.iter().try_fold(value, |value, segment| match value {
+    serde_json::Value::Object(map) => map.get(*segment),
+    serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+    _ => None,
+  })
+}
+
+/// Set a value at the given path creating the missing objects, returns false if the path cannot be reached
+#[cfg(feature = "json")]
+fn json_set(value: &mut serde_json::Value, segments: &[&str], new: serde_json::Value) -> bool {
+  let Some((first, rest)) = segments.split_first() else {
+    *value = new;
+    return true;
+  };
+
+  if value.is_null() {
+    *value = serde_json::Value::Object(Default::default());
+  }
+
+  let next = match value {
+    serde_json::Value::Object(map) => map.entry(first.to_string()).or_insert(serde_json::Value::Null),
+    serde_json::Value::Array(items) => match first.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+      Some(item) => item,
+      None => return false,
+    },
+    _ => return false,
+  };
+
+  json_set(next, rest, new)
+}
+
+#[cfg(feature = "json")]
+fn diff_values(
+  path: &str,
+  left: &serde_json::Value,
+  right: &serde_json::Value,
+  changed: &mut Vec<String>,
+) {
+  use serde_json::Value;
+
+  match (left, right) {
+    (Value::Object(left), Value::Object(right)) => {
+      let mut keys = left.keys().collect::<Vec<_>>();
+      for key in right.keys() {
+        if !left.contains_key(key) {
+          keys.push(key);
+        }
+      }
+
+      for key in keys {
+        let path = util::join_path(path, key);
+        match (left.get(key), right.get(key)) {
+          (Some(left), Some(right)) => diff_values(&path, left, right, changed),
+          _ => changed.push(path),
+        }
+      }
+    }
+
+    (left, right) => {
+      if left != right {
+        changed.push(path.to_string());
+      }
+    }
+  }
 }
 
 impl<T: Config> Default for ConfigLoader<T> {
@@ -1,16 +1,27 @@
 //! Utility functions to use with `#[config(merge)]` attribute
 
+use crate::error::MergeError;
+use crate::util::join_path;
+use crate::PartialConfig;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::Infallible;
+use std::hash::Hash;
 
 /// Utility function to use with `#[config(merge)]` attribute
 ///
 /// this function will append a vector to the previos one instead of replacing it
 ///
+/// the items are kept in the order of the stages, eg: the items of a file stage and then the items of a later env stage,
+/// it can be combined with a list `parse_env` function to append lists loaded from env
+///
 /// usage:
 ///
 /// ```text
 /// #[config(merge = metre::merge::append_vec)]
 /// my_field: Vec<T>
+///
+/// #[config(merge = metre::merge::append_vec, parse_env = metre::parse::comma_separated::<T>)]
+/// my_env_field: Vec<T>
 /// ```
 pub fn append_vec<T>(left: &mut Option<Vec<T>>, right: Option<Vec<T>>) -> Result<(), Infallible> {
   if let Some(left_vec) = left {
@@ -23,3 +34,158 @@ pub fn append_vec<T>(left: &mut Option<Vec<T>>, right: Option<Vec<T>>) -> Result
 
   Ok(())
 }
+
+/// Utility function to use with `#[config(merge)]` attribute
+///
+/// this function will insert the entries of the new map into the previous one instead of replacing it,
+/// the values of keys present in both maps are replaced with the new ones
+///
+/// usage:
+///
+/// ```text
+/// #[config(merge = metre::merge::extend_map)]
+/// my_field: HashMap<K, V>
+/// ```
+pub fn extend_map<K: Eq + Hash, V>(
+  left: &mut Option<HashMap<K, V>>,
+  right: Option<HashMap<K, V>>,
+) -> Result<(), Infallible> {
+  if let Some(left_map) = left {
+    if let Some(right_map) = right {
+      left_map.extend(right_map);
+    }
+  } else if let Some(right_map) = right {
+    *left = Some(right_map);
+  };
+
+  Ok(())
+}
+
+/// Utility function to use with `#[config(merge)]` attribute
+///
+/// this function will deep merge the values of matching keys of a map of partial configurations
+/// instead of replacing the previous value, keys that are not present in the previous map are inserted
+///
+/// usage:
+///
+/// ```text
+/// #[config(merge = metre::merge::deep_merge_map, skip_env)]
+/// my_field: HashMap<String, <T as Config>::Partial>
+/// ```
+pub fn deep_merge_map<K: Eq + Hash + ToString, V: PartialConfig>(
+  left: &mut Option<HashMap<K, V>>,
+  right: Option<HashMap<K, V>>,
+) -> Result<(), MergeError> {
+  if let Some(left_map) = left {
+    if let Some(right_map) = right {
+      for (key, value) in right_map {
+        match left_map.get_mut(&key) {
+          Some(left_value) => left_value.merge(value).map_err(|e| MergeError {
+            field: join_path(&key.to_string(), &e.field),
+            message: e.message,
+            source: e.source,
+          })?,
+          None => {
+            left_map.insert(key, value);
+          }
+        }
+      }
+    }
+  } else if let Some(right_map) = right {
+    *left = Some(right_map);
+  };
+
+  Ok(())
+}
+
+/// A value that can be empty, used by [`keep_nonempty`]
+pub trait IsEmpty {
+  /// Returns true if this value is empty
+  fn is_empty(&self) -> bool;
+}
+
+macro_rules! impl_is_empty {
+  ($($ty:ty => [$($generics:tt)*])*) => {
+    $(
+      impl<$($generics)*> IsEmpty for $ty {
+        #[inline(always)]
+        fn is_empty(&self) -> bool {
+          <$ty>::is_empty(self)
+        }
+      }
+    )*
+  }
+}
+
+impl_is_empty!(
+  String => []
+  Vec<T> => [T]
+  HashMap<K, V> => [K, V]
+  HashSet<T> => [T]
+  BTreeMap<K, V> => [K, V]
+  BTreeSet<T> => [T]
+);
+
+/// Utility function to use with `#[config(merge)]` attribute
+///
+/// this function will only replace the previous value if the new value is present and not empty
+///
+/// this is useful to prevent empty env variables from overriding values from previous stages
+///
+/// usage:
+///
+/// ```text
+/// #[config(merge = metre::merge::keep_nonempty)]
+/// my_field: String
+/// ```
+pub fn keep_nonempty<T: IsEmpty>(left: &mut Option<T>, right: Option<T>) -> Result<(), Infallible> {
+  if let Some(right) = right {
+    if !right.is_empty() {
+      *left = Some(right);
+    }
+  }
+
+  Ok(())
+}
+
+/// Utility function to use with `#[config(merge)]` attribute
+///
+/// this function will keep the largest of the previous and the new value
+///
+/// usage:
+///
+/// ```text
+/// #[config(merge = metre::merge::max)]
+/// max_connections: usize
+/// ```
+pub fn max<T: Ord>(left: &mut Option<T>, right: Option<T>) -> Result<(), Infallible> {
+  if let Some(right) = right {
+    match left {
+      Some(left) if *left >= right => {}
+      _ => *left = Some(right),
+    }
+  }
+
+  Ok(())
+}
+
+/// Utility function to use with `#[config(merge)]` attribute
+///
+/// this function will keep the smallest of the previous and the new value
+///
+/// usage:
+///
+/// ```text
+/// #[config(merge = metre::merge::min)]
+/// timeout_ms: u64
+/// ```
+pub fn min<T: Ord>(left: &mut Option<T>, right: Option<T>) -> Result<(), Infallible> {
+  if let Some(right) = right {
+    match left {
+      Some(left) if *left <= right => {}
+      _ => *left = Some(right),
+    }
+  }
+
+  Ok(())
+}
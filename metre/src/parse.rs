@@ -1,5 +1,10 @@
 //! Utility functions to use with `#[config(parse_env)]` attribute
 
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::num::{IntErrorKind, ParseIntError};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 /// Utility function to use with `#[config(parse_env)]` attribute
@@ -17,13 +22,154 @@ use std::str::FromStr;
 /// my_field: Vec<T>
 /// ```
 pub fn comma_separated<T: FromStr>(value: &str) -> Result<Option<Vec<T>>, T::Err> {
+  list_with(value, ',', |item| item.parse::<T>())
+}
+
+/// Utility function to use with `#[config(parse_env)]` attribute
+///
+/// same as [`comma_separated`] but trims the whitespace around each item before parsing it
+///
+/// usage:
+///
+/// ```text
+/// #[config(parse_env = metre::parse::comma_separated_trimmed::<T>)]
+/// my_field: Vec<T>
+/// ```
+pub fn comma_separated_trimmed<T: FromStr>(value: &str) -> Result<Option<Vec<T>>, T::Err> {
+  list_with(value, ',', |item| item.trim().parse::<T>())
+}
+
+/// Helper to write custom `#[config(parse_env)]` functions for lists
+///
+/// the function will split the env string by `delimiter` and parse each item with `parse`
+///
+/// usage:
+///
+/// ```text
+/// fn parse_lowercase(value: &str) -> Result<Option<Vec<String>>, Infallible> {
+///   metre::parse::list_with(value, ';', |item| Ok(item.to_lowercase()))
+/// }
+///
+/// #[config(parse_env = parse_lowercase)]
+/// my_field: Vec<String>
+/// ```
+pub fn list_with<T, E, F: Fn(&str) -> Result<T, E>>(
+  value: &str,
+  delimiter: char,
+  parse: F,
+) -> Result<Option<Vec<T>>, E> {
   let mut target = vec![];
   if !value.is_empty() {
-    for item in value.split(',') {
-      let parsed = item.parse::<T>()?;
+    for item in value.split(delimiter) {
+      let parsed = parse(item)?;
       target.push(parsed);
     }
   }
 
   Ok(Some(target))
 }
+
+/// Utility function to use with `#[config(parse_env)]` attribute
+///
+/// the function will return a [`Vec<PathBuf>`] from a `PATH` like env string,
+/// splitted by the platform path separator (`:` on unix and `;` on windows) with [`std::env::split_paths`]
+///
+/// usage:
+///
+/// ```text
+/// #[config(parse_env = metre::parse::path_list)]
+/// my_field: Vec<PathBuf>
+/// ```
+pub fn path_list(value: &str) -> Result<Option<Vec<PathBuf>>, Infallible> {
+  if value.is_empty() {
+    return Ok(Some(vec![]));
+  }
+
+  Ok(Some(std::env::split_paths(value).collect()))
+}
+
+/// Utility function to use with `#[config(parse_env)]` attribute
+///
+/// the function will return a [`HashMap<String, String>`] from a comma separated list of `key=value` pairs,
+/// eg: `APP_LABELS=env=prod,team=core`
+///
+/// each pair is splitted at the first `=` so values can contain `=` characters,
+/// an entry without a `=` is an error, and an empty string produces an empty map
+///
+/// the env map replaces the map of the previous stages, use [`crate::merge::extend_map`] to merge them instead
+///
+/// usage:
+///
+/// ```text
+/// #[config(parse_env = metre::parse::key_value_pairs, merge = metre::merge::extend_map)]
+/// labels: HashMap<String, String>
+/// ```
+pub fn key_value_pairs(value: &str) -> Result<Option<HashMap<String, String>>, String> {
+  let mut target = HashMap::new();
+  if !value.is_empty() {
+    for item in value.split(',') {
+      match item.split_once('=') {
+        Some((key, value)) => {
+          target.insert(key.to_string(), value.to_string());
+        }
+        None => {
+          return Err(format!(
+            "invalid entry {item:?}, expected a key value pair like key=value"
+          ))
+        }
+      }
+    }
+  }
+
+  Ok(Some(target))
+}
+
+/// Integer types that can be parsed with [`bounded_int`]
+pub trait BoundedInt: FromStr<Err = ParseIntError> + Display {
+  /// The smallest value of this integer type
+  const MIN: Self;
+  /// The largest value of this integer type
+  const MAX: Self;
+}
+
+macro_rules! impl_bounded_int {
+  ($($ty:ty),*) => {
+    $(
+      impl BoundedInt for $ty {
+        const MIN: Self = <$ty>::MIN;
+        const MAX: Self = <$ty>::MAX;
+      }
+    )*
+  };
+}
+
+impl_bounded_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// Utility function to use with `#[config(parse_env)]` attribute
+///
+/// same as the default [`FromStr`] parsing for integers but the error message includes
+/// the received value and the valid range of the type
+///
+/// usage:
+///
+/// ```text
+/// #[config(parse_env = metre::parse::bounded_int::<u16>)]
+/// port: u16
+/// ```
+pub fn bounded_int<T: BoundedInt>(value: &str) -> Result<Option<T>, String> {
+  match value.parse::<T>() {
+    Ok(v) => Ok(Some(v)),
+    Err(e) => match e.kind() {
+      IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => Err(format!(
+        "value {value:?} is out of range, expected an integer between {} and {}",
+        T::MIN,
+        T::MAX
+      )),
+      _ => Err(format!(
+        "value {value:?} is not a valid integer, expected an integer between {} and {}",
+        T::MIN,
+        T::MAX
+      )),
+    },
+  }
+}
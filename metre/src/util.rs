@@ -1,8 +1,16 @@
 #![doc(hidden)]
 
 use crate::error::MergeError;
+#[cfg(feature = "env")]
+use crate::error::FromEnvError;
+#[cfg(feature = "env")]
+use crate::EnvProvider;
 use crate::PartialConfig;
+use serde::{Deserialize, Deserializer};
 use std::convert::Infallible;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
 
 pub trait UnOption {
   type T;
@@ -12,6 +20,102 @@ impl<T> UnOption for Option<T> {
   type T = T;
 }
 
+/// The message and the optional source of an error, see [`ErrorSource`]
+pub type MessageAndSource = (String, Option<Arc<dyn std::error::Error + Send + Sync>>);
+
+/// Wrapper used by the derive macro to convert the errors of `#[config(merge)]` and `#[config(parse_env)]` functions
+/// into [`MergeError`] and [`FromEnvError`](crate::error::FromEnvError)
+///
+/// errors that implement [`std::error::Error`] are kept as the source of the error
+/// and any other error that implements [`Display`] is converted to a message only
+pub struct ErrorSource<E>(pub E);
+
+/// See [`ErrorSource`]
+pub trait ErrorSourceFromError {
+  fn into_message_and_source(self) -> MessageAndSource;
+}
+
+impl<E: std::error::Error + Send + Sync + 'static> ErrorSourceFromError for ErrorSource<E> {
+  fn into_message_and_source(self) -> MessageAndSource {
+    (self.0.to_string(), Some(Arc::new(self.0)))
+  }
+}
+
+/// See [`ErrorSource`]
+pub trait ErrorSourceFromDisplay {
+  fn into_message_and_source(self) -> MessageAndSource;
+}
+
+impl<E: Display> ErrorSourceFromDisplay for &ErrorSource<E> {
+  fn into_message_and_source(self) -> MessageAndSource {
+    (self.0.to_string(), None)
+  }
+}
+
+/// Build the [`MergeError`] of a `#[config(merge)]` function from its converted error, see [`ErrorSource`]
+///
+/// if the function returns a [`MergeError`] its field is a sub-path of the merged field, eg: the map key in
+/// [`deep_merge_map`](crate::merge::deep_merge_map), and it is joined to `field` to keep the deep path
+pub fn custom_merge_error(field: &str, (message, source): MessageAndSource) -> MergeError {
+  match source.as_deref().and_then(|e| e.downcast_ref::<MergeError>()) {
+    Some(e) => MergeError {
+      field: join_path(field, &e.field),
+      message: e.message.clone(),
+      source: e.source.clone(),
+    },
+    None => MergeError {
+      field: field.to_string(),
+      message,
+      source,
+    },
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ValueOrString<T> {
+  Value(T),
+  String(String),
+}
+
+impl<T: FromStr> ValueOrString<T>
+where
+  T::Err: Display,
+{
+  fn into_value<E: serde::de::Error>(self) -> Result<T, E> {
+    match self {
+      ValueOrString::Value(v) => Ok(v),
+      ValueOrString::String(s) => s.parse::<T>().map_err(|e| E::custom(format!("invalid value {s:?}: {e}"))),
+    }
+  }
+}
+
+/// Deserializer used by the `#[config(coerce_strings)]` field attribute
+///
+/// accepts the value itself or a string that is parsed with [`FromStr`], like the values loaded from env
+pub fn coerce_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de> + FromStr,
+  T::Err: Display,
+{
+  let v = ValueOrString::<T>::deserialize(deserializer)?;
+  v.into_value().map(Some)
+}
+
+/// Same as [`coerce_string`] for `Option<T>` fields
+pub fn coerce_string_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de> + FromStr,
+  T::Err: Display,
+{
+  match Option::<ValueOrString<T>>::deserialize(deserializer)? {
+    None => Ok(None),
+    Some(v) => v.into_value().map(|v| Some(Some(v))),
+  }
+}
+
 #[inline(always)]
 pub fn merge_flat<T>(left: &mut Option<T>, right: Option<T>) -> Result<(), Infallible> {
   if let Some(right) = right {
@@ -24,3 +128,122 @@ pub fn merge_flat<T>(left: &mut Option<T>, right: Option<T>) -> Result<(), Infal
 pub fn merge_nested<T: PartialConfig>(left: &mut T, right: T) -> Result<(), MergeError> {
   left.merge(right)
 }
+
+/// Join a field name with the deep path of a nested property
+///
+/// indexed paths like `[0].port` are joined without a dot: eg: `servers[0].port`
+/// and an empty field name (used for flattened fields) returns the path as is
+#[inline(always)]
+pub fn join_path(field: &str, path: &str) -> String {
+  if field.is_empty() {
+    path.to_string()
+  } else if path.is_empty() {
+    field.to_string()
+  } else if path.starts_with('[') {
+    format!("{}{}", field, path)
+  } else {
+    format!("{}.{}", field, path)
+  }
+}
+
+/// Get the rest of a deep property path after the field path, returns `None` if the property is not under the field
+///
+/// this is the inverse of [`join_path`], an empty field matches every property
+pub fn strip_path<'a>(property: &'a str, field: &str) -> Option<&'a str> {
+  if field.is_empty() {
+    return Some(property);
+  }
+
+  let rest = property.strip_prefix(field)?;
+  if let Some(rest) = rest.strip_prefix('.') {
+    Some(rest)
+  } else if rest.starts_with('[') {
+    Some(rest)
+  } else {
+    None
+  }
+}
+
+/// Fill the `{}` placeholders of an env key format with the prefix
+///
+/// all the placeholders are filled with the same prefix and `{{` and `}}` are unescaped to literal braces,
+/// formats without placeholders are returned as fixed keys
+pub fn fill_env_fmt(fmt: &str, prefix: &str) -> String {
+  let mut target = String::with_capacity(fmt.len() + prefix.len());
+  let mut chars = fmt.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      ('{', Some('}')) => {
+        chars.next();
+        target.push_str(prefix);
+      }
+      ('{', Some('{')) | ('}', Some('}')) => {
+        chars.next();
+        target.push(c);
+      }
+      _ => target.push(c),
+    }
+  }
+
+  target
+}
+
+/// The maximum number of items that [`env_seq`] will load from env
+#[cfg(feature = "env")]
+pub const ENV_SEQ_MAX_COUNT: usize = 4096;
+
+/// Load a list of nested partial configurations from env for `#[config(env_seq)]` fields
+///
+/// the number of items is read from `{prefix}COUNT` and each item is loaded with the `{prefix}{index}_` prefix,
+/// if the count variable is not set an empty list is returned
+///
+/// a count greater than [`ENV_SEQ_MAX_COUNT`] is reported as an error
+#[cfg(feature = "env")]
+pub fn env_seq<P: PartialConfig, E: EnvProvider>(
+  env: &E,
+  prefix: &str,
+) -> Result<Vec<P>, FromEnvError> {
+  let count_key = format!("{}COUNT", prefix);
+
+  let count = env.get(&count_key).map_err(|e| FromEnvError {
+    key: count_key.clone(),
+    field: String::new(),
+    message: e.to_string(),
+    source: None,
+  })?;
+
+  let count = match count {
+    None => return Ok(vec![]),
+    Some(count) => count.parse::<usize>().map_err(|e| FromEnvError {
+      key: count_key.clone(),
+      field: String::new(),
+      message: e.to_string(),
+      source: Some(Arc::new(e)),
+    })?,
+  };
+
+  if count > ENV_SEQ_MAX_COUNT {
+    return Err(FromEnvError {
+      key: count_key,
+      field: String::new(),
+      message: format!("count {} is greater than the maximum of {}", count, ENV_SEQ_MAX_COUNT),
+      source: None,
+    });
+  }
+
+  let mut items = vec![];
+  for i in 0..count {
+    let item = P::from_env_with_provider_and_prefix(env, format!("{}{}_", prefix, i)).map_err(|e| {
+      FromEnvError {
+        key: e.key,
+        field: join_path(&format!("[{}]", i), &e.field),
+        message: e.message,
+        source: e.source,
+      }
+    })?;
+
+    items.push(item);
+  }
+
+  Ok(items)
+}
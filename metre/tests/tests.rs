@@ -850,7 +850,7 @@ fn should_load_json_file() {
   std::fs::write(&path, "{\"port\": 3000}").unwrap();
 
   let mut loader = ConfigLoader::<Conf>::new();
-  loader.file(path.to_str().unwrap(), Format::Json).unwrap();
+  loader.file(&path, Format::Json).unwrap();
   let config = loader.finish().unwrap();
 
   assert_eq!(config.port, 3000);
@@ -881,7 +881,7 @@ fn should_load_jsonc_file() {
   .unwrap();
 
   let mut loader = ConfigLoader::<Conf>::new();
-  loader.file(path.to_str().unwrap(), Format::Jsonc).unwrap();
+  loader.file(&path, Format::Jsonc).unwrap();
   let config = loader.finish().unwrap();
 
   assert_eq!(config.port, 3000);
@@ -910,7 +910,7 @@ fn should_load_toml_file() {
   .unwrap();
 
   let mut loader = ConfigLoader::<Conf>::new();
-  loader.file(path.to_str().unwrap(), Format::Toml).unwrap();
+  loader.file(&path, Format::Toml).unwrap();
   let config = loader.finish().unwrap();
 
   assert_eq!(config.port, 3000);
@@ -939,9 +939,3797 @@ fn should_load_yaml_file() {
   .unwrap();
 
   let mut loader = ConfigLoader::<Conf>::new();
-  loader.file(path.to_str().unwrap(), Format::Yaml).unwrap();
+  loader.file(&path, Format::Yaml).unwrap();
   let config = loader.finish().unwrap();
 
   assert_eq!(config.port, 3000);
   assert_eq!(config.addr, "addr");
 }
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_embedded_file() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .embedded(metre::embed!("embedded.toml", Format::Toml))
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, 3000);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_report_embedded_path_on_error() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .embedded(metre::embed!("embedded.toml", Format::Toml))
+    .unwrap_err();
+
+  assert!(err.to_string().contains("embedded.toml"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_report_named_location_on_error() {
+  use metre::LoadLocation;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .code_with_location(
+      r#"{ "port": "3000" }"#,
+      Format::Json,
+      LoadLocation::named("remote override"),
+    )
+    .unwrap_err();
+
+  assert!(err.to_string().contains("remote override"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_start_from_defaults_with_defaults_constructor() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    #[config(default = "addr".into())]
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::with_defaults().unwrap();
+  loader.code("port = 3001", Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3001,
+      addr: "addr".into(),
+    }
+  );
+}
+
+#[test]
+fn should_error_instead_of_panic_on_nested_from_partial_error() {
+  use metre::error::{FromPartialError, MergeError};
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    #[config(nested)]
+    nested: Strict,
+  }
+
+  // a config that doesn't report its missing properties up front
+  #[derive(Debug)]
+  struct Strict {
+    #[allow(unused)]
+    value: u16,
+  }
+
+  #[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+  struct PartialStrict {
+    value: Option<u16>,
+  }
+
+  impl PartialConfig for PartialStrict {
+    fn defaults() -> Self {
+      Self::default()
+    }
+
+    fn merge(&mut self, other: Self) -> Result<(), MergeError> {
+      if other.value.is_some() {
+        self.value = other.value;
+      }
+      Ok(())
+    }
+
+    fn list_missing_properties(&self) -> Vec<String> {
+      vec![]
+    }
+
+    fn is_empty(&self) -> bool {
+      self.value.is_none()
+    }
+
+    fn from_env_with_provider_and_optional_prefix<E: metre::EnvProvider>(
+      _env: &E,
+      _prefix: Option<&str>,
+    ) -> Result<Self, metre::error::FromEnvError> {
+      Ok(Self::default())
+    }
+  }
+
+  impl Config for Strict {
+    type Partial = PartialStrict;
+    fn from_partial(partial: Self::Partial) -> Result<Self, FromPartialError> {
+      match partial.value {
+        Some(value) => Ok(Self { value }),
+        None => Err(FromPartialError::new(
+          std::any::type_name::<Self>(),
+          vec!["value".into()],
+        )),
+      }
+    }
+  }
+
+  let loader = ConfigLoader::<Conf>::new();
+  let err = loader.finish().unwrap_err();
+
+  match err {
+    metre::Error::FromPartial(e) => assert_eq!(e.missing_properties, ["nested.value"]),
+    _ => panic!("expected a FromPartial error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_deep_merge_maps_of_nested_configs() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(merge = metre::merge::deep_merge_map, skip_env)]
+    services: HashMap<String, PartialService>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Service {
+    port: u16,
+    host: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        [services.web]
+        port = 3000
+
+        [services.api]
+        port = 4000
+        host = "api"
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+  loader
+    .code(
+      r#"
+        [services.web]
+        host = "web"
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+
+  let mut services = loader.finish().unwrap().services;
+  let web = Service::from_partial(services.remove("web").unwrap()).unwrap();
+  let api = Service::from_partial(services.remove("api").unwrap()).unwrap();
+
+  assert_eq!(web, Service { port: 3000, host: "web".into() });
+  assert_eq!(api, Service { port: 4000, host: "api".into() });
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_from_env_only() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    addr: String,
+  }
+
+  std::env::set_var("METRE_TEST_FROM_ENV_ONLY_ADDR", "addr");
+
+  let config = ConfigLoader::<Conf>::from_env_with_prefix("METRE_TEST_FROM_ENV_ONLY_").unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      addr: "addr".into(),
+    }
+  );
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_error_on_unknown_env_with_env_strict() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_strict, env_prefix = "{}CONF_")]
+  struct Conf {
+    port: u16,
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("MY_APP_CONF_PORT", "3000");
+  env.insert("MY_APP_CONF_NESTED_ADDR", "addr");
+  env.insert("OTHER", "other");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .env_with_provider_and_prefix(&env, "MY_APP_")
+    .unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.nested.addr, "addr");
+
+  env.insert("MY_APP_CONF_NESTED_PORT", "3000");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .env_with_provider_and_prefix(&env, "MY_APP_")
+    .unwrap_err();
+
+  match err {
+    metre::Error::FromEnv(e) => assert_eq!(e.key, "MY_APP_CONF_NESTED_PORT"),
+    _ => panic!("expected a FromEnv error, got {err:?}"),
+  }
+}
+
+#[cfg(all(feature = "toml", feature = "env"))]
+#[test]
+fn should_not_override_with_empty_values_with_keep_nonempty() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(merge = metre::merge::keep_nonempty)]
+    addr: String,
+    #[config(merge = metre::merge::keep_nonempty, parse_env = metre::parse::comma_separated::<String>)]
+    list: Vec<String>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("ADDR", "");
+  env.insert("LIST", "");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        addr = "addr"
+        list = ["item"]
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+  loader.env_with_provider(&env).unwrap();
+  loader.code(r#"addr = "other""#, Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      addr: "other".into(),
+      list: vec!["item".into()],
+    }
+  );
+}
+
+#[cfg(all(feature = "toml", feature = "env"))]
+#[test]
+fn should_explain_loaded_stages() {
+  use metre::LoadLocation;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    addr: String,
+  }
+
+  let env = HashMap::<String, String>::new();
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  loader
+    .code_with_location("port = 3001", Format::Toml, LoadLocation::named("override"))
+    .unwrap();
+  loader
+    .env_with_provider_and_prefix(&env, "MY_APP_")
+    .unwrap();
+
+  let layers = loader.layers();
+  assert_eq!(layers.len(), 3);
+  assert_eq!(layers[0].location, LoadLocation::Defaults);
+  assert!(!layers[1].is_empty);
+  assert_eq!(layers[2].location, LoadLocation::Env(Some("MY_APP_".into())));
+  assert!(layers[2].is_empty);
+
+  let explain = loader.explain();
+  assert!(explain.contains("defaults"));
+  assert!(explain.contains("override"));
+  assert!(explain.contains("MY_APP_"));
+  assert!(explain.contains("(empty)"));
+  assert!(explain.contains("missing properties"));
+  assert!(explain.contains("addr"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_validate_each_item_of_nested_lists() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested)]
+    servers: Vec<Server>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Server {
+    host: String,
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        [[servers]]
+        host = "a"
+        port = 3000
+
+        [[servers]]
+        host = "b"
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+
+  let missing = loader.partial_state().list_missing_properties();
+  assert_eq!(missing, ["servers[1].port"]);
+  loader.finish().unwrap_err();
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        [[servers]]
+        host = "a"
+        port = 3000
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config.servers,
+    [Server {
+      host: "a".into(),
+      port: 3000
+    }]
+  );
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_nested_lists_from_env_with_env_seq() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested, env_seq)]
+    servers: Vec<Server>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Server {
+    port: u16,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("SERVERS_COUNT", "2");
+  env.insert("SERVERS_0_PORT", "8080");
+  env.insert("SERVERS_1_PORT", "8081");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config.servers,
+    [Server { port: 8080 }, Server { port: 8081 }]
+  );
+
+  assert_eq!(PartialConf::env_keys_with_optional_prefix(None), ["SERVERS_COUNT"]);
+
+  let env = HashMap::<&str, &str>::new();
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.servers, []);
+
+  let mut env = HashMap::new();
+  env.insert("SERVERS_COUNT", "1");
+  env.insert("SERVERS_0_PORT", "not-a-port");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.env_with_provider(&env).unwrap_err();
+  match err {
+    metre::error::Error::FromEnv(e) => {
+      assert_eq!(e.key, "SERVERS_0_PORT");
+      assert_eq!(e.field, "servers[0].port");
+    }
+    _ => panic!("expected FromEnv error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_error_on_huge_env_seq_count() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(nested, env_seq)]
+    #[allow(unused)]
+    servers: Vec<Server>,
+  }
+
+  #[derive(Config, Debug)]
+  struct Server {
+    #[allow(unused)]
+    port: Option<u16>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("SERVERS_COUNT", "18446744073709551615");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.env_with_provider(&env).unwrap_err();
+  match err {
+    metre::error::Error::FromEnv(e) => {
+      assert_eq!(e.key, "SERVERS_COUNT");
+    }
+    _ => panic!("expected FromEnv error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_override_env_prefix_at_runtime() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_prefix = "{}CONF_")]
+  struct Conf {
+    port: u16,
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("MY_APP_CONF_PORT", "3000");
+  env.insert("MY_APP_OTHER_PORT", "3001");
+  env.insert("MY_APP_OTHER_NESTED_ADDR", "addr");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .env_prefix_override("{}OTHER_")
+    .env_with_provider_and_prefix(&env, "MY_APP_")
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, 3001);
+  assert_eq!(config.nested.addr, "addr");
+}
+
+#[cfg(all(feature = "env", feature = "json"))]
+#[test]
+fn should_get_partial_from_env_in_isolation() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("MY_APP_PORT", "3000");
+
+  let partial = ConfigLoader::<Conf>::partial_from_env_with_provider(&env, Some("MY_APP_")).unwrap();
+
+  let serialized = serde_json::to_string(&partial).unwrap();
+  assert_eq!(serialized, "{\"port\":3000}");
+}
+
+#[test]
+fn should_include_type_name_in_missing_properties_error() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct MyAppConf {
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    port: u16,
+  }
+
+  let loader = ConfigLoader::<MyAppConf>::new();
+  let err = loader.finish().unwrap_err();
+
+  match &err {
+    metre::Error::FromPartial(e) => {
+      assert!(e.type_name.ends_with("MyAppConf"));
+      assert_eq!(e.missing_properties, ["nested.port"]);
+    }
+    _ => panic!("expected a FromPartial error, got {err:?}"),
+  }
+
+  assert!(err.to_string().contains("MyAppConf"));
+}
+
+#[cfg(all(feature = "toml", feature = "env"))]
+#[test]
+fn should_rename_env_keys_independently_with_rename_all_env() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(rename_all = "camelCase", rename_all_env = "snake_case")]
+  struct Conf {
+    listen_port: u16,
+    listen_addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("listen_addr", "addr");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("listenPort = 3000", Format::Toml).unwrap();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      listen_port: 3000,
+      listen_addr: "addr".into(),
+    }
+  );
+
+  // the env keys are the same as without rename_all, even with digits in the field names
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  #[config(rename_all = "camelCase", rename_all_env = "SCREAMING_SNAKE_CASE")]
+  struct Renamed {
+    api_v2_url: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Plain {
+    api_v2_url: String,
+  }
+
+  assert_eq!(PartialRenamed::default().list_missing_properties(), ["apiV2Url"]);
+  assert_eq!(
+    PartialRenamed::env_keys_with_optional_prefix(None),
+    PartialPlain::env_keys_with_optional_prefix(None)
+  );
+}
+
+#[test]
+fn should_detect_formats() {
+  #[cfg(feature = "json")]
+  assert_eq!(Format::detect(r#"  { "port": 3000 }"#), Some(Format::Json));
+  #[cfg(feature = "jsonc")]
+  assert_eq!(
+    Format::detect("{\n  // comment\n  \"port\": 3000\n}"),
+    Some(Format::Jsonc)
+  );
+  #[cfg(feature = "toml")]
+  {
+    assert_eq!(Format::detect("# comment\nport = 3000"), Some(Format::Toml));
+    assert_eq!(Format::detect("[nested]\nport = 3000"), Some(Format::Toml));
+    assert_eq!(Format::detect(r#"url = "http://host""#), Some(Format::Toml));
+    assert_eq!(Format::detect("[db.primary] # comment\nport = 3000"), Some(Format::Toml));
+    assert_eq!(Format::detect("[[servers]]\nport = 3000"), Some(Format::Toml));
+  }
+  #[cfg(feature = "yaml")]
+  {
+    assert_eq!(Format::detect("---\nport: 3000"), Some(Format::Yaml));
+    assert_eq!(Format::detect("port: 3000"), Some(Format::Yaml));
+    assert_eq!(Format::detect("[a, b]"), Some(Format::Yaml));
+  }
+  // a top level array is not a TOML table header
+  #[cfg(feature = "json")]
+  assert_eq!(Format::detect("[1,2]"), Some(Format::Json));
+  #[cfg(all(feature = "yaml", not(feature = "json")))]
+  assert_eq!(Format::detect("[1,2]"), Some(Format::Yaml));
+  assert_eq!(Format::detect("port"), None);
+  assert_eq!(Format::detect(""), None);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_code_with_detected_format() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code_detect("port = 3000").unwrap();
+  assert_eq!(loader.finish().unwrap().port, 3000);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code_detect("port").unwrap_err();
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_trim_comma_separated_items() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(parse_env = metre::parse::comma_separated_trimmed::<String>)]
+    list: Vec<String>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("LIST", " a , b ");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.list, ["a", "b"]);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_list_defaulted_fields() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    #[config(default = "addr".into())]
+    addr: String,
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    #[config(default = 1)]
+    a: u8,
+    #[config(default = 2)]
+    b: u8,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  loader
+    .code(
+      r#"
+        port = 3001
+        nested.b = 3
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+
+  assert_eq!(loader.defaulted_fields(), ["addr", "nested.a"]);
+}
+
+#[test]
+fn should_respect_defaults_for_optional_fields() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 8080)]
+    port: Option<u16>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, Some(8080));
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_env_vars_set_in_the_loader() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.set_env("PORT", "3000").set_env("ADDR", "addr");
+  loader.apply_env().unwrap();
+  assert_eq!(loader.partial_state().port, Some(3000));
+
+  loader.set_env("PORT", "3001");
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3001,
+      addr: "addr".into(),
+    }
+  );
+}
+
+#[cfg(all(feature = "toml", feature = "env"))]
+#[test]
+fn should_flatten_nested_configs() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_prefix = "{}APP_")]
+  struct Conf {
+    name: String,
+    #[config(flatten, nested)]
+    server: Server,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Server {
+    port: u16,
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_ADDR", "addr");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        name = "name"
+        port = 3000
+        "#,
+      Format::Toml,
+    )
+    .unwrap();
+
+  assert_eq!(loader.partial_state().list_missing_properties(), ["addr"]);
+
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      name: "name".into(),
+      server: Server {
+        port: 3000,
+        addr: "addr".into(),
+      }
+    }
+  );
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code(r#"other = "other""#, Format::Toml).unwrap_err();
+}
+
+#[test]
+fn should_finish_in_an_arc() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+  }
+
+  let config = ConfigLoader::<Conf>::with_defaults()
+    .unwrap()
+    .finish_arc()
+    .unwrap();
+
+  let shared = std::sync::Arc::clone(&config);
+  assert_eq!(shared.port, 3000);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_report_field_and_value_on_invalid_type() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .code(r#"{ "nested": { "port": "3001" } }"#, Format::Json)
+    .unwrap_err();
+
+  match &err {
+    metre::Error::InvalidField { path, error, .. } => {
+      assert_eq!(path, "nested.port");
+      assert!(matches!(**error, metre::Error::Json { .. }));
+    }
+    _ => panic!("expected an InvalidField error, got {err:?}"),
+  }
+
+  let message = err.to_string();
+  assert!(message.contains("nested.port"));
+  assert!(message.contains("3001"));
+
+  // the received value is reported only once in the error chain
+  let mut chain = vec![];
+  let mut current: Option<&dyn std::error::Error> = Some(&err);
+  while let Some(e) = current {
+    chain.push(e.to_string());
+    current = e.source();
+  }
+  assert_eq!(chain.iter().filter(|m| m.contains("3001")).count(), 1);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_report_field_on_invalid_toml_type() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.code(r#"port = "3001""#, Format::Toml).unwrap_err();
+
+  assert_eq!(err.field_path(), Some("port"));
+  match &err {
+    metre::Error::InvalidField { error, .. } => assert!(matches!(**error, metre::Error::Toml { .. })),
+    _ => panic!("expected an InvalidField error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_env_from_dotenv_file() {
+  use metre::{DotenvFileEnv, EnvProvider};
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let path = std::env::temp_dir().as_path().join("metre-test.env");
+  std::fs::write(
+    &path,
+    r#"
+      # a comment
+      MY_APP_PORT=3000
+      export MY_APP_ADDR="some addr"
+      OTHER='other'
+      "#,
+  )
+  .unwrap();
+
+  let env = DotenvFileEnv::new(&path).unwrap();
+  assert_eq!(env.get("OTHER").unwrap(), Some("other".into()));
+  assert_eq!(env.keys().unwrap(), ["MY_APP_ADDR", "MY_APP_PORT", "OTHER"]);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .env_with_provider_and_prefix(&env, "MY_APP_")
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      addr: "some addr".into(),
+    }
+  );
+
+  let path = std::env::temp_dir().as_path().join("metre-test-invalid.env");
+  std::fs::write(&path, "MY_APP_PORT").unwrap();
+  DotenvFileEnv::new(&path).unwrap_err();
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_parse_quoted_dotenv_values() {
+  use metre::{DotenvFileEnv, EnvProvider};
+
+  let path = std::env::temp_dir().as_path().join("metre-test-quoted.env");
+  std::fs::write(
+    &path,
+    concat!(
+      "COMMENTED=\"x\" # a comment\n",
+      "SINGLE_COMMENTED='y' # a comment\n",
+      "NEWLINE=\"a\\nb\"\n",
+      "ESCAPED_BACKSLASH=\"a\\\\nb\"\n",
+      "ESCAPED_QUOTE=\"say \\\"hi\\\"\"\n",
+      "HASH_INSIDE=\"a # b\"\n",
+      "SINGLE_RAW='a\\nb'\n",
+    ),
+  )
+  .unwrap();
+
+  let env = DotenvFileEnv::new(&path).unwrap();
+  assert_eq!(env.get("COMMENTED").unwrap(), Some("x".into()));
+  assert_eq!(env.get("SINGLE_COMMENTED").unwrap(), Some("y".into()));
+  assert_eq!(env.get("NEWLINE").unwrap(), Some("a\nb".into()));
+  assert_eq!(env.get("ESCAPED_BACKSLASH").unwrap(), Some("a\\nb".into()));
+  assert_eq!(env.get("ESCAPED_QUOTE").unwrap(), Some("say \"hi\"".into()));
+  assert_eq!(env.get("HASH_INSIDE").unwrap(), Some("a # b".into()));
+  assert_eq!(env.get("SINGLE_RAW").unwrap(), Some("a\\nb".into()));
+
+  for code in ["KEY=\"unterminated", "KEY=\"x\" trailing", "KEY=\"ends with escape\\\""] {
+    std::fs::write(&path, code).unwrap();
+    match DotenvFileEnv::new(&path).unwrap_err() {
+      metre::error::DotenvError::Parse { line, .. } => assert_eq!(line, 1),
+      e => panic!("expected a Parse error for {code}, got {e:?}"),
+    }
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_chain_env_providers() {
+  use metre::{ChainEnv, EnvProvider};
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut first = HashMap::new();
+  first.insert("PORT", "3000");
+
+  let mut second = HashMap::new();
+  second.insert("PORT", "3001");
+  second.insert("ADDR", "addr");
+
+  let env = ChainEnv(first, second);
+
+  let mut keys = env.keys().unwrap();
+  keys.sort();
+  assert_eq!(keys, ["ADDR", "PORT"]);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      addr: "addr".into(),
+    }
+  );
+}
+
+#[test]
+fn should_get_format_from_content_type() {
+  #[cfg(feature = "json")]
+  assert_eq!(
+    Format::from_content_type("application/json; charset=utf-8"),
+    Some(Format::Json)
+  );
+  #[cfg(feature = "toml")]
+  assert_eq!(Format::from_content_type("application/toml"), Some(Format::Toml));
+  #[cfg(feature = "yaml")]
+  {
+    assert_eq!(Format::from_content_type("text/yaml"), Some(Format::Yaml));
+    assert_eq!(Format::from_content_type("Application/X-YAML"), Some(Format::Yaml));
+  }
+  assert_eq!(Format::from_content_type("text/html"), None);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_build_errors_from_source_errors() {
+  use metre::LoadLocation;
+
+  #[allow(clippy::result_large_err)]
+  fn load_port(code: &str) -> Result<u16, metre::Error> {
+    #[derive(serde::Deserialize)]
+    struct Port {
+      port: u16,
+    }
+
+    let port: Port = toml::from_str(code)
+      .map_err(|e| metre::Error::from_toml(e, LoadLocation::named("port loader")))?;
+
+    Ok(port.port)
+  }
+
+  assert_eq!(load_port("port = 3000").unwrap(), 3000);
+  let err = load_port("port = \"3000\"").unwrap_err();
+  assert!(err.to_string().contains("port loader"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_skip_fields() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+    #[config(skip)]
+    computed: Vec<u8>,
+    #[config(skip, default = String::from("local"))]
+    label: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "port": 3000 }"#, metre::Format::Json)
+    .unwrap();
+  let conf = loader.finish().unwrap();
+
+  assert_eq!(conf.port, 3000);
+  assert!(conf.computed.is_empty());
+  assert_eq!(conf.label, "local");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  assert!(loader
+    .code(r#"{ "port": 3000, "computed": [1] }"#, metre::Format::Json)
+    .is_err());
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_parse_bounded_ints_with_range_messages() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(parse_env = metre::parse::bounded_int::<u16>)]
+    port: u16,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("PORT", "3000");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  assert_eq!(loader.finish().unwrap().port, 3000);
+
+  env.insert("PORT", "99999");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  match loader.env_with_provider(&env).unwrap_err() {
+    metre::Error::FromEnv(e) => {
+      assert_eq!(e.key, "PORT");
+      assert!(e.message.contains("\"99999\" is out of range"));
+      assert!(e.message.contains("65535"));
+    }
+    e => panic!("unexpected error {e}"),
+  }
+
+  env.insert("PORT", "http");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  match loader.env_with_provider(&env).unwrap_err() {
+    metre::Error::FromEnv(e) => assert!(e.message.contains("\"http\" is not a valid integer")),
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_load_from_json_value() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .value(serde_json::json!({ "port": 3000, "addr": "localhost" }))
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.addr, "localhost");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  match loader.value(serde_json::json!({ "port": "3000" })).unwrap_err() {
+    metre::Error::InvalidField { path, .. } => assert_eq!(path, "port"),
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[cfg(all(feature = "env", feature = "json"))]
+#[test]
+fn should_load_boxed_nested_configs() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested)]
+    nested: Box<Nested>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Nested {
+    port: u16,
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("NESTED_ADDR", "localhost");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "nested": { "port": 3000 } }"#, metre::Format::Json)
+    .unwrap();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.nested.port, 3000);
+  assert_eq!(config.nested.addr, "localhost");
+
+  let loader = ConfigLoader::<Conf>::new();
+  let e = loader.finish().unwrap_err();
+  match e {
+    metre::Error::FromPartial(e) => {
+      assert_eq!(e.missing_properties, ["nested.port", "nested.addr"]);
+    }
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_load_recursive_boxed_configs() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Node {
+    name: String,
+    #[config(nested, skip_env)]
+    next: Option<Box<Node>>,
+  }
+
+  let mut loader = ConfigLoader::<Node>::new();
+  loader
+    .code(
+      r#"{ "name": "a", "next": { "name": "b", "next": { "name": "c" } } }"#,
+      metre::Format::Json,
+    )
+    .unwrap();
+  loader.code(r#"{ "next": { "name": "B" } }"#, metre::Format::Json).unwrap();
+  let node = loader.finish().unwrap();
+
+  assert_eq!(
+    node,
+    Node {
+      name: "a".into(),
+      next: Some(Box::new(Node {
+        name: "B".into(),
+        next: Some(Box::new(Node {
+          name: "c".into(),
+          next: None,
+        })),
+      })),
+    }
+  );
+
+  let mut loader = ConfigLoader::<Node>::new();
+  loader
+    .code(r#"{ "name": "a", "next": { "next": { "name": "c" } } }"#, metre::Format::Json)
+    .unwrap();
+  match loader.finish().unwrap_err() {
+    metre::Error::FromPartial(e) => assert_eq!(e.missing_properties, ["next.name"]),
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[test]
+fn should_display_load_locations_with_and_without_color() {
+  use metre::LoadLocation;
+
+  let location = LoadLocation::File("config.toml".into());
+
+  #[cfg(feature = "color")]
+  assert_eq!(location.to_string(), "file: \u{1b}[33mconfig.toml\u{1b}[39m");
+
+  #[cfg(not(feature = "color"))]
+  assert_eq!(location.to_string(), "file: config.toml");
+}
+
+#[cfg(all(feature = "toml", feature = "env"))]
+#[test]
+fn should_count_applied_layers() {
+  use metre::LoadLocation;
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    #[config(default = 3000)]
+    port: u16,
+  }
+
+  let env = HashMap::<String, String>::new();
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  assert_eq!(loader.layers_applied(), 0);
+
+  loader.defaults().unwrap();
+  loader.code("port = 3001", Format::Toml).unwrap();
+  loader.env_with_provider(&env).unwrap();
+
+  assert_eq!(loader.layers_applied(), 3);
+  assert_eq!(
+    loader.layer_locations(),
+    [
+      &LoadLocation::Defaults,
+      &LoadLocation::Memory,
+      &LoadLocation::Env(None)
+    ]
+  );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_collect_extra_fields_in_flatten_extra_map() {
+  #[derive(Config, Debug, PartialEq)]
+  struct Conf {
+    port: u16,
+    #[config(flatten_extra)]
+    extra: HashMap<String, serde_json::Value>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "port": 3000, "foo": "bar", "baz": 1 }"#, metre::Format::Json)
+    .unwrap();
+  loader
+    .code(r#"{ "baz": 2, "qux": true }"#, metre::Format::Json)
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.extra.len(), 3);
+  assert_eq!(config.extra["foo"], serde_json::json!("bar"));
+  assert_eq!(config.extra["baz"], serde_json::json!(2));
+  assert_eq!(config.extra["qux"], serde_json::json!(true));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_access_error_kinds() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: u16,
+  }
+
+  let err = ConfigLoader::<Conf>::new().finish().unwrap_err();
+  assert!(err.is_missing_properties());
+  assert_eq!(err.missing_properties(), Some(&["port".to_string()][..]));
+  assert!(err.as_io().is_none());
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .file("metre-this-file-does-not-exist.toml", Format::Toml)
+    .unwrap_err();
+  assert!(!err.is_missing_properties());
+  assert_eq!(err.missing_properties(), None);
+  assert_eq!(err.as_io().unwrap().kind(), std::io::ErrorKind::NotFound);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_parse_path_lists() {
+  use std::path::PathBuf;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(parse_env = metre::parse::path_list)]
+    paths: Vec<PathBuf>,
+  }
+
+  let joined = std::env::join_paths(["/usr/bin", "/opt/bin"]).unwrap();
+
+  let mut env = HashMap::new();
+  env.insert("PATHS".to_string(), joined.to_str().unwrap().to_string());
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.paths, [PathBuf::from("/usr/bin"), PathBuf::from("/opt/bin")]);
+
+  env.insert("PATHS".to_string(), String::new());
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert!(config.paths.is_empty());
+}
+
+#[test]
+fn should_load_blank_sources_as_empty_stages() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: Option<u16>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+
+  #[cfg(feature = "json")]
+  loader.code(" \n ", Format::Json).unwrap();
+  #[cfg(feature = "toml")]
+  loader.code("# only a comment\n\n", Format::Toml).unwrap();
+  #[cfg(feature = "yaml")]
+  loader.code("", Format::Yaml).unwrap();
+  #[cfg(feature = "yaml")]
+  loader.code("  # only a comment\n", Format::Yaml).unwrap();
+  #[cfg(feature = "jsonc")]
+  loader.code("// only a comment\n/* and a block */", Format::Jsonc).unwrap();
+
+  assert!(loader.layers().iter().all(|layer| layer.is_empty));
+  assert!(loader.finish().unwrap().port.is_none());
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_env_with_optional_prefix() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("PORT", "3000");
+  env.insert("MY_APP_ADDR", "localhost");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_optional_prefix(&env, None).unwrap();
+  loader
+    .env_with_provider_and_optional_prefix(&env, Some("MY_APP_"))
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.addr, "localhost");
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_fill_env_formats_with_braces_and_placeholders() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(env = "{}LITERAL_{{BRACES}}")]
+    literal: String,
+    #[config(env = "FIXED_PORT")]
+    port: u16,
+    #[config(env = "{}TWICE_{}")]
+    twice: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_LITERAL_{BRACES}", "literal");
+  env.insert("FIXED_PORT", "3000");
+  env.insert("APP_TWICE_APP_", "twice");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_prefix(&env, "APP_").unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      literal: "literal".into(),
+      port: 3000,
+      twice: "twice".into(),
+    }
+  );
+}
+
+#[test]
+fn should_chain_error_sources() {
+  use std::error::Error as _;
+  #[allow(unused)]
+  use std::sync::Arc;
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: u16,
+  }
+
+  // shared sources are wrapped in an Arc to keep the errors Clone
+  #[allow(unused)]
+  fn source_of<'a>(err: &'a metre::Error) -> &'a (dyn std::error::Error + 'static) {
+    // type errors at a field wrap the deserializer error
+    let err = match err {
+      metre::Error::InvalidField { error, .. } => error,
+      err => err,
+    };
+    err.source().expect("error should have a source")
+  }
+
+  #[cfg(feature = "json")]
+  {
+    let mut loader = ConfigLoader::<Conf>::new();
+    let err = loader.code("{ \"port\": true }", Format::Json).unwrap_err();
+    assert!(source_of(&err).is::<Arc<serde_json::Error>>());
+  }
+
+  #[cfg(feature = "jsonc")]
+  {
+    let mut loader = ConfigLoader::<Conf>::new();
+    let err = loader.code("// comment\n{ \"port\": true }", Format::Jsonc).unwrap_err();
+    assert!(source_of(&err).is::<Arc<serde_json::Error>>());
+  }
+
+  #[cfg(feature = "toml")]
+  {
+    let mut loader = ConfigLoader::<Conf>::new();
+    let err = loader.code("port = true", Format::Toml).unwrap_err();
+    assert!(source_of(&err).is::<toml::de::Error>());
+
+    let mut loader = ConfigLoader::<Conf>::new();
+    let err = loader
+      .file("metre-this-file-does-not-exist.toml", Format::Toml)
+      .unwrap_err();
+    assert!(source_of(&err).is::<Arc<std::io::Error>>());
+  }
+
+  #[cfg(feature = "yaml")]
+  {
+    let mut loader = ConfigLoader::<Conf>::new();
+    let err = loader.code("port: true", Format::Yaml).unwrap_err();
+    assert!(source_of(&err).is::<Arc<serde_yaml::Error>>());
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_expose_chain_env_error_sources() {
+  use metre::error::ChainEnvError;
+  use std::error::Error as _;
+
+  let err = ChainEnvError::<std::io::Error, std::fmt::Error>::First(std::io::Error::other("first"));
+  assert_eq!(err.to_string(), "first");
+  assert!(err.source().unwrap().is::<std::io::Error>());
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_from_a_list_of_sources() {
+  use metre::Source;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    addr: String,
+  }
+
+  let config = ConfigLoader::<Conf>::load(&[
+    Source::Defaults,
+    Source::FileOptional("metre-this-file-does-not-exist.toml".into(), Format::Toml),
+    Source::Code("addr = \"localhost\"".into(), Format::Toml),
+  ])
+  .unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      addr: "localhost".into(),
+    }
+  );
+
+  let err = ConfigLoader::<Conf>::load(&[Source::Defaults]).unwrap_err();
+  assert_eq!(err.missing_properties(), Some(&["addr".to_string()][..]));
+}
+
+#[cfg(all(feature = "tracing", feature = "toml"))]
+#[test]
+fn should_emit_tracing_events() {
+  use std::sync::{Arc, Mutex};
+  use tracing::field::{Field, Visit};
+  use tracing::span::{Attributes, Id, Record};
+  use tracing::{Event, Metadata, Subscriber};
+
+  // records the level and the fields of every event
+  #[derive(Default)]
+  struct Recorder(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+
+  struct Fields(String);
+
+  impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+      self.0.push_str(&format!("{}={:?} ", field.name(), value));
+    }
+  }
+
+  impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+      true
+    }
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+      let mut fields = Fields(format!("span={} ", span.metadata().name()));
+      span.record(&mut fields);
+      self.0.lock().unwrap().push((*span.metadata().level(), fields.0));
+      Id::from_u64(1)
+    }
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, event: &Event<'_>) {
+      let mut fields = Fields(String::new());
+      event.record(&mut fields);
+      self.0.lock().unwrap().push((*event.metadata().level(), fields.0));
+    }
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    host: String,
+    #[config(deprecated = "use host instead")]
+    addr: Option<String>,
+  }
+
+  let recorder = Recorder::default();
+  let events = Arc::clone(&recorder.0);
+
+  tracing::subscriber::with_default(recorder, || {
+    let mut loader = ConfigLoader::<Conf>::with_defaults().unwrap();
+    loader.code("host = \"localhost\"", Format::Toml).unwrap();
+    loader.code("host = \"0.0.0.0\"\naddr = \"0.0.0.0\"", Format::Toml).unwrap();
+    loader.finish().unwrap();
+  });
+
+  let events = events.lock().unwrap();
+  assert!(events
+    .iter()
+    .any(|(level, fields)| *level == tracing::Level::TRACE && fields.contains("parsing config stage")));
+  assert!(events
+    .iter()
+    .any(|(level, fields)| *level == tracing::Level::DEBUG && fields.contains("config stage loaded")));
+  assert!(events.iter().any(|(level, fields)| {
+    *level == tracing::Level::WARN && fields.contains("field=port") && fields.contains("default value")
+  }));
+  assert!(!events.iter().any(|(_, fields)| fields.contains("field=host")));
+
+  // one span per stage
+  assert_eq!(
+    events.iter().filter(|(_, fields)| fields.starts_with("span=config_stage ")).count(),
+    3
+  );
+  assert!(events.iter().any(|(_, fields)| {
+    fields.contains("config stage loaded") && fields.contains("added=[\"host\"]") && fields.contains("overridden=[]")
+  }));
+  assert!(events.iter().any(|(_, fields)| {
+    fields.contains("config stage loaded")
+      && fields.contains("added=[\"addr\"]")
+      && fields.contains("overridden=[\"host\"]")
+  }));
+  assert_eq!(
+    events
+      .iter()
+      .filter(|(level, fields)| {
+        *level == tracing::Level::WARN
+          && fields.contains("deprecated config field is set")
+          && fields.contains("field=addr")
+          && fields.contains("note=use host instead")
+      })
+      .count(),
+    1
+  );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_list_deprecated_properties() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    #[config(deprecated = "use listen instead")]
+    addr: Option<String>,
+    #[config(nested)]
+    db: Db,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    url: Option<String>,
+    #[config(deprecated)]
+    pool_size: Option<u32>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("[db]\nurl = \"postgres://\"", Format::Toml).unwrap();
+  assert!(loader.partial_state().list_deprecated_properties().is_empty());
+
+  loader.code("addr = \"0.0.0.0\"\n[db]\npool_size = 4", Format::Toml).unwrap();
+  assert_eq!(
+    loader.partial_state().list_deprecated_properties(),
+    [
+      ("addr".to_string(), "use listen instead".to_string()),
+      ("db.pool_size".to_string(), String::new()),
+    ]
+  );
+}
+
+#[cfg(all(feature = "json", feature = "toml"))]
+#[test]
+fn should_only_fill_missing_properties_with_finish_or_default() {
+  #[derive(Config, Debug, serde::Serialize)]
+  struct Conf {
+    port: u16,
+    #[config(skip)]
+    cache: u32,
+    #[config(merge = metre::merge::append_vec, skip_env)]
+    hosts: Vec<String>,
+    #[config(nested)]
+    db: Db,
+  }
+
+  #[derive(Config, Debug, serde::Serialize)]
+  struct Db {
+    url: String,
+    pool: u32,
+  }
+
+  impl Default for Conf {
+    fn default() -> Self {
+      Self {
+        port: 3000,
+        cache: 10,
+        hosts: vec![String::from("default")],
+        db: Db {
+          url: String::from("postgres://default"),
+          pool: 4,
+        },
+      }
+    }
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code("hosts = [\"x\"]\n[db]\npool = 8", Format::Toml)
+    .unwrap();
+  let config = loader.finish_or_default().unwrap();
+
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.hosts, ["x"]);
+  assert_eq!(config.db.url, "postgres://default");
+  assert_eq!(config.db.pool, 8);
+}
+
+#[cfg(all(feature = "env", feature = "json", feature = "toml"))]
+#[test]
+fn should_report_errors_of_finish_or_default_like_finish() {
+  #[derive(Config, Debug, Default, serde::Serialize)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    #[config(conflicts_with = "password_file")]
+    password: Option<String>,
+    password_file: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code("password = \"a\"\npassword_file = \"b\"", Format::Toml)
+    .unwrap();
+  loader.verbose_missing();
+  let err = loader.finish_or_default().unwrap_err();
+  assert!(matches!(err, metre::Error::MissingPropertiesVerbose { .. }));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("password = \"a\"", Format::Toml).unwrap();
+  loader.set_env("PORT", "80");
+  let config = loader.finish_or_default().unwrap();
+  assert_eq!(config.port, 80);
+}
+
+#[cfg(all(feature = "json", feature = "toml"))]
+#[test]
+fn should_finish_with_missing_properties_from_default() {
+  #[derive(Config, Debug, Eq, PartialEq, serde::Serialize)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  impl Default for Conf {
+    fn default() -> Self {
+      Self {
+        port: 3000,
+        addr: "0.0.0.0".into(),
+      }
+    }
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("addr = \"localhost\"", Format::Toml).unwrap();
+  let config = loader.finish_or_default().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      addr: "localhost".into(),
+    }
+  );
+}
+
+#[cfg(all(feature = "env", feature = "yaml"))]
+#[test]
+fn should_load_config_blob_from_env_var() {
+  use metre::LoadLocation;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_CONFIG_YAML", "port: 3000\naddr: localhost\n");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .env_blob_with_provider(&env, "APP_CONFIG_YAML", Format::Yaml)
+    .unwrap();
+  loader
+    .env_blob_with_provider(&env, "APP_CONFIG_MISSING", Format::Yaml)
+    .unwrap();
+
+  assert_eq!(
+    loader.layer_locations(),
+    [&LoadLocation::EnvVar("APP_CONFIG_YAML".into())]
+  );
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.addr, "localhost");
+}
+
+#[test]
+fn should_merge_with_max_and_min() {
+  let mut value = Some(5);
+  metre::merge::max(&mut value, Some(3)).unwrap();
+  assert_eq!(value, Some(5));
+  metre::merge::max(&mut value, Some(8)).unwrap();
+  assert_eq!(value, Some(8));
+  metre::merge::max(&mut value, None).unwrap();
+  assert_eq!(value, Some(8));
+
+  let mut value = None;
+  metre::merge::min(&mut value, Some(5)).unwrap();
+  assert_eq!(value, Some(5));
+  metre::merge::min(&mut value, Some(3)).unwrap();
+  assert_eq!(value, Some(3));
+  metre::merge::min(&mut value, Some(8)).unwrap();
+  assert_eq!(value, Some(3));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_merge_fields_with_max() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(merge = metre::merge::max)]
+    max_connections: usize,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("max_connections = 5", Format::Toml).unwrap();
+  loader.code("max_connections = 3", Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.max_connections, 5);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_convert_partial_into_config() {
+  use metre::PartialConfig;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("PORT", "3000");
+
+  let config = PartialConf::from_env_with_provider(&env)
+    .unwrap()
+    .into_config()
+    .unwrap();
+  assert_eq!(config, Conf { port: 3000 });
+
+  let err = PartialConf::default().into_config().unwrap_err();
+  assert_eq!(err.missing_properties, ["port"]);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_skip_env_with_env_false() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(env = false)]
+    skipped: Option<u16>,
+    #[config(env = true)]
+    enabled: Option<u16>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("SKIPPED", "1");
+  env.insert("ENABLED", "2");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      skipped: None,
+      enabled: Some(2),
+    }
+  );
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_load_shared_nested_configs() {
+  use std::rc::Rc;
+  use std::sync::Arc;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested)]
+    db: Arc<Db>,
+    #[config(nested)]
+    cache: Rc<Db>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Db {
+    url: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"{ "db": { "url": "postgres://" }, "cache": { "url": "redis://" } }"#,
+      metre::Format::Json,
+    )
+    .unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.db.url, "postgres://");
+  assert_eq!(config.cache.url, "redis://");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "cache": { "url": "redis://" } }"#, metre::Format::Json)
+    .unwrap();
+  let err = loader.finish().unwrap_err();
+  assert_eq!(err.missing_properties(), Some(&["db.url".to_string()][..]));
+}
+
+#[cfg(all(feature = "json", feature = "toml"))]
+#[test]
+fn should_diff_partial_configurations() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    timeout: u64,
+    name: String,
+    #[config(nested)]
+    log: Log,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Log {
+    level: String,
+    file: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      "timeout = 10\nname = \"app\"\n[log]\nlevel = \"info\"",
+      Format::Toml,
+    )
+    .unwrap();
+
+  let mut reloaded = ConfigLoader::<Conf>::new();
+  reloaded
+    .code(
+      "timeout = 20\nname = \"app\"\n[log]\nlevel = \"debug\"\nfile = \"app.log\"",
+      Format::Toml,
+    )
+    .unwrap();
+
+  assert_eq!(
+    loader.diff(reloaded.partial_state()).unwrap(),
+    ["log.level", "log.file", "timeout"]
+  );
+  assert!(loader.diff(loader.partial_state()).unwrap().is_empty());
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Points {
+    // JSON maps can only have string keys
+    #[config(skip_env)]
+    names: std::collections::BTreeMap<(u8, u8), String>,
+  }
+
+  let mut loader = ConfigLoader::<Points>::new();
+  loader.partial_state_mut().names = Some([((0, 0), String::from("origin"))].into());
+  let err = loader.diff(&PartialPoints::default()).unwrap_err();
+  assert!(matches!(err, metre::Error::Json { .. }));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_serialize_and_deserialize_formats() {
+  assert_eq!(serde_json::to_string(&Format::Json).unwrap(), "\"json\"");
+  assert_eq!(serde_json::from_str::<Format>("\"json\"").unwrap(), Format::Json);
+
+  #[cfg(feature = "jsonc")]
+  assert_eq!(serde_json::from_str::<Format>("\"jsonc\"").unwrap(), Format::Jsonc);
+
+  #[cfg(feature = "toml")]
+  {
+    assert_eq!(serde_json::to_string(&Format::Toml).unwrap(), "\"toml\"");
+
+    #[derive(Config, Debug)]
+    struct Conf {
+      output: Format,
+    }
+
+    let mut loader = ConfigLoader::<Conf>::new();
+    loader.code("output = \"toml\"", Format::Toml).unwrap();
+    assert_eq!(loader.finish().unwrap().output, Format::Toml);
+  }
+
+  #[cfg(feature = "yaml")]
+  assert_eq!(serde_json::to_string(&Format::Yaml).unwrap(), "\"yaml\"");
+
+  assert!(serde_json::from_str::<Format>("\"xml\"").is_err());
+  assert!("xml".parse::<Format>().is_err());
+  assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+}
+
+#[cfg(feature = "url-blocking")]
+#[test]
+fn should_give_up_url_retries_with_network_error() {
+  use std::time::{Duration, Instant};
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: u16,
+  }
+
+  let start = Instant::now();
+  let mut loader = ConfigLoader::<Conf>::new();
+  // nothing is listening on port 1, so every attempt fails with a connection error
+  let err = loader
+    .url_with_retry("http://127.0.0.1:1/config.json", Format::Json, 2, Duration::from_millis(10))
+    .unwrap_err();
+
+  assert!(matches!(err, metre::Error::Network { .. }));
+  // 10ms + 20ms of backoff
+  assert!(start.elapsed() >= Duration::from_millis(30));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_apply_closures_to_the_partial_state() {
+  use std::convert::Infallible;
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code("port = 3000", Format::Toml)
+    .unwrap()
+    .apply(|partial| {
+      partial.addr = Some(format!("localhost:{}", partial.port.unwrap()));
+      Ok::<_, Infallible>(())
+    })
+    .unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.addr, "localhost:3000");
+
+  // errors of other crates are kept as the source
+  #[derive(Debug)]
+  struct MissingPort;
+
+  impl std::fmt::Display for MissingPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "port is required to compute addr")
+    }
+  }
+
+  impl std::error::Error for MissingPort {}
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .apply(|partial| match partial.port {
+      Some(port) => {
+        partial.addr = Some(format!("localhost:{}", port));
+        Ok(())
+      }
+      None => Err(MissingPort),
+    })
+    .unwrap_err();
+
+  match &err {
+    metre::Error::Apply { message, source } => {
+      assert_eq!(message, "port is required to compute addr");
+      assert!(source.is::<MissingPort>());
+    }
+    _ => panic!("expected an apply error, got {:?}", err),
+  }
+  assert!(err.to_string().contains("port is required to compute addr"));
+}
+
+#[cfg(all(feature = "env", feature = "json"))]
+#[test]
+fn should_derive_env_keys_from_renamed_fields() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(rename_all = "camelCase")]
+  struct Conf {
+    max_connections: u16,
+    #[config(rename = "listenAddr")]
+    addr: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "maxConnections": 10 }"#, Format::Json)
+    .unwrap();
+
+  let mut env = HashMap::new();
+  env.insert("LISTEN_ADDR", "localhost");
+  loader.env_with_provider(&env).unwrap();
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.max_connections, 10);
+  assert_eq!(config.addr, "localhost");
+
+  let mut env = HashMap::new();
+  env.insert("MAX_CONNECTIONS", "20");
+  env.insert("LISTEN_ADDR", "localhost");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  assert_eq!(loader.finish().unwrap().max_connections, 20);
+}
+
+#[cfg(all(feature = "env", feature = "json"))]
+#[test]
+fn should_derive_env_keys_from_kebab_case_fields() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(rename_all = "kebab-case")]
+  struct Conf {
+    my_field: u16,
+    #[config(rename = "other-field")]
+    other: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "my-field": 1, "other-field": 2 }"#, Format::Json)
+    .unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config, Conf { my_field: 1, other: 2 });
+
+  let mut env = HashMap::new();
+  env.insert("MY_FIELD", "3");
+  env.insert("OTHER_FIELD", "4");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config, Conf { my_field: 3, other: 4 });
+
+  let err = ConfigLoader::<Conf>::new().finish().unwrap_err();
+  assert_eq!(
+    err.missing_properties(),
+    Some(&["my-field".to_string(), "other-field".to_string()][..])
+  );
+}
+
+#[cfg(feature = "env")]
+#[test]
+#[allow(non_snake_case)]
+fn should_derive_env_keys_from_serde_names_with_lowercase() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(rename_all = "lowercase")]
+  struct Conf {
+    maxConnections: u16,
+  }
+
+  // serde doesn't change the case of the fields with lowercase, it expects snake_case fields
+  assert_eq!(PartialConf::default().list_missing_properties(), ["maxConnections"]);
+
+  let mut env = HashMap::new();
+  env.insert("MAX_CONNECTIONS", "3");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  assert_eq!(loader.finish().unwrap().maxConnections, 3);
+}
+
+#[test]
+fn should_load_code_with_format_names() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: Option<u16>,
+  }
+
+  #[cfg(feature = "json")]
+  assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+  #[cfg(feature = "jsonc")]
+  assert_eq!("jsonc".parse::<Format>().unwrap(), Format::Jsonc);
+  #[cfg(feature = "toml")]
+  assert_eq!("TOML".parse::<Format>().unwrap(), Format::Toml);
+  #[cfg(feature = "yaml")]
+  {
+    assert_eq!("yaml".parse::<Format>().unwrap(), Format::Yaml);
+    assert_eq!("yml".parse::<Format>().unwrap(), Format::Yaml);
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+
+  #[cfg(feature = "toml")]
+  loader.code_str("port = 3000", "toml").unwrap();
+
+  match loader.code_str("<port>3000</port>", "xml").unwrap_err() {
+    metre::Error::ParseFormat(e) => {
+      assert_eq!(e.name, "xml");
+      assert!(e.to_string().contains("xml"));
+    }
+    e => panic!("unexpected error {e}"),
+  }
+}
+
+#[test]
+fn should_reject_non_integral_numbers_for_integer_fields() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: u16,
+  }
+
+  #[allow(unused)]
+  let cases: &[(&str, Format)] = &[
+    #[cfg(feature = "json")]
+    (r#"{ "port": 3000.5 }"#, Format::Json),
+    #[cfg(feature = "json")]
+    (r#"{ "port": 3000.0 }"#, Format::Json),
+    #[cfg(feature = "jsonc")]
+    (r#"{ "port": 3000.5 }"#, Format::Jsonc),
+    #[cfg(feature = "toml")]
+    ("port = 3000.5", Format::Toml),
+    #[cfg(feature = "toml")]
+    ("port = 3000.0", Format::Toml),
+    #[cfg(feature = "yaml")]
+    ("port: 3000.5", Format::Yaml),
+    #[cfg(feature = "yaml")]
+    ("port: 3000.0", Format::Yaml),
+  ];
+
+  for (code, format) in cases {
+    let mut loader = ConfigLoader::<Conf>::new();
+    assert!(
+      loader.code(code, *format).is_err(),
+      "{code:?} should not load in format {format:?}"
+    );
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_validate_the_whole_config_with_finish_validated() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    min: u32,
+    max: u32,
+  }
+
+  let validate = |conf: &Conf| {
+    if conf.min >= conf.max {
+      Err(format!("min ({}) must be less than max ({})", conf.min, conf.max))
+    } else {
+      Ok(())
+    }
+  };
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code(r#"{ "min": 1, "max": 10 }"#, Format::Json).unwrap();
+  let config = loader.finish_validated(validate).unwrap();
+  assert_eq!(config.min, 1);
+  assert_eq!(config.max, 10);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code(r#"{ "min": 10, "max": 10 }"#, Format::Json).unwrap();
+  let err = loader.finish_validated(validate).unwrap_err();
+  match &err {
+    metre::error::Error::Validation { message } => {
+      assert_eq!(message, "min (10) must be less than max (10)");
+    }
+    _ => panic!("expected Validation error, got {err:?}"),
+  }
+  assert!(err.to_string().contains("min (10) must be less than max (10)"));
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_include_env_keys_of_missing_properties_with_verbose_missing() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    #[config(nested)]
+    db: Db,
+    #[config(skip_env)]
+    name: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    url: String,
+  }
+
+  let env = HashMap::<&str, &str>::new();
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_optional_prefix(&env, Some("APP_")).unwrap();
+  loader.verbose_missing();
+  let err = loader.finish().unwrap_err();
+
+  assert_eq!(err.missing_properties().unwrap(), ["port", "db.url", "name"]);
+  match &err {
+    metre::error::Error::MissingPropertiesVerbose { env_keys, .. } => {
+      assert_eq!(
+        env_keys,
+        &[
+          Some(String::from("APP_PORT")),
+          Some(String::from("APP_DB_URL")),
+          None
+        ]
+      );
+    }
+    _ => panic!("expected MissingPropertiesVerbose error, got {err:?}"),
+  }
+
+  let message = err.to_string();
+  assert!(message.contains("APP_PORT"));
+  assert!(message.contains("APP_DB_URL"));
+
+  // default errors stay terse
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_optional_prefix(&env, Some("APP_")).unwrap();
+  let err = loader.finish().unwrap_err();
+  assert!(matches!(err, metre::error::Error::FromPartial(_)));
+  assert!(!err.to_string().contains("APP_PORT"));
+}
+
+#[cfg(all(feature = "env", feature = "toml"))]
+#[test]
+fn should_parse_key_value_pairs() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(parse_env = metre::parse::key_value_pairs, merge = metre::merge::extend_map)]
+    labels: HashMap<String, String>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("LABELS", "env=prod,team=core");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      r#"
+        [labels]
+        region = "eu"
+        env = "dev"
+      "#,
+      Format::Toml,
+    )
+    .unwrap();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.labels.len(), 3);
+  assert_eq!(config.labels["env"], "prod");
+  assert_eq!(config.labels["team"], "core");
+  assert_eq!(config.labels["region"], "eu");
+
+  env.insert("LABELS", "env=prod,team");
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.env_with_provider(&env).unwrap_err();
+  assert!(err.to_string().contains("team"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_keep_typed_merge_errors_as_source() {
+  #[derive(Debug, thiserror::Error)]
+  #[error("port {0} is already set")]
+  struct PortConflict(u16);
+
+  fn no_override(left: &mut Option<u16>, right: Option<u16>) -> Result<(), PortConflict> {
+    match (left.as_ref(), right) {
+      (Some(_), Some(right)) => Err(PortConflict(right)),
+      (None, right) => {
+        *left = right;
+        Ok(())
+      }
+      _ => Ok(()),
+    }
+  }
+
+  fn no_override_str(left: &mut Option<u16>, right: Option<u16>) -> Result<(), String> {
+    no_override(left, right).map_err(|e| e.to_string())
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    #[config(merge = no_override)]
+    port: u16,
+    #[config(merge = no_override_str)]
+    other_port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 3000", Format::Toml).unwrap();
+  let err = loader.code("port = 3001", Format::Toml).unwrap_err();
+  let merge_err = match &err {
+    metre::Error::Merge(e) => e,
+    _ => panic!("expected Merge error, got {err:?}"),
+  };
+  assert_eq!(merge_err.field, "port");
+  assert_eq!(merge_err.message, "port 3001 is already set");
+  let source = merge_err.source.as_ref().unwrap();
+  assert_eq!(source.downcast_ref::<PortConflict>().unwrap().0, 3001);
+  let source = std::error::Error::source(merge_err).unwrap();
+  assert_eq!(source.to_string(), "port 3001 is already set");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 3000\nother_port = 1", Format::Toml).unwrap();
+  let err = loader.code("other_port = 2", Format::Toml).unwrap_err();
+  match &err {
+    metre::Error::Merge(e) => {
+      assert_eq!(e.message, "port 2 is already set");
+      assert!(e.source.is_none());
+    }
+    _ => panic!("expected Merge error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_generate_env_docs() {
+  #[derive(Config, Debug)]
+  #[config(env_prefix = "{}APP_")]
+  #[allow(unused)]
+  struct Conf {
+    /// The port to listen on
+    port: u16,
+    /// The public url of the app,
+    /// used in emails | links
+    #[config(default = String::from("http://localhost"))]
+    public_url: String,
+    #[config(nested)]
+    db: Option<Db>,
+    #[config(skip_env)]
+    secret: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    /// The database url
+    url: String,
+  }
+
+  let vars = PartialConf::env_vars_with_optional_prefix(None);
+  assert_eq!(
+    vars,
+    [
+      metre::EnvVar {
+        key: "APP_PORT".into(),
+        field: "port".into(),
+        required: true,
+        doc: "The port to listen on".into(),
+      },
+      metre::EnvVar {
+        key: "APP_PUBLIC_URL".into(),
+        field: "public_url".into(),
+        required: false,
+        doc: "The public url of the app,\nused in emails | links".into(),
+      },
+      metre::EnvVar {
+        key: "APP_DB_URL".into(),
+        field: "db.url".into(),
+        required: false,
+        doc: "The database url".into(),
+      },
+    ]
+  );
+
+  let docs = ConfigLoader::<Conf>::env_docs();
+  assert_eq!(
+    docs,
+    "| Variable | Field | Required | Description |\n\
+     | --- | --- | --- | --- |\n\
+     | `APP_PORT` | `port` | yes | The port to listen on |\n\
+     | `APP_PUBLIC_URL` | `public_url` | no | The public url of the app, used in emails \\| links |\n\
+     | `APP_DB_URL` | `db.url` | no | The database url |\n"
+  );
+}
+
+#[test]
+fn should_capture_field_doc_comments() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    /// The port to listen on
+    port: u16,
+    /// The database configuration
+    ///
+    /// Only postgres is supported
+    #[config(nested)]
+    db: Db,
+    undocumented: Option<String>,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    /// The database url
+    url: String,
+  }
+
+  let docs = PartialConf::field_docs();
+  assert_eq!(
+    docs,
+    [
+      (String::from("port"), String::from("The port to listen on")),
+      (
+        String::from("db"),
+        String::from("The database configuration\n\nOnly postgres is supported")
+      ),
+      (String::from("db.url"), String::from("The database url")),
+    ]
+  );
+}
+
+#[test]
+fn should_coerce_quoted_strings_with_coerce_strings() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(coerce_strings)]
+    port: u16,
+    #[config(coerce_strings)]
+    debug: Option<bool>,
+  }
+
+  let sources: Vec<(&str, Format)> = vec![
+    #[cfg(feature = "toml")]
+    ("port = \"3000\"\ndebug = \"true\"", Format::Toml),
+    #[cfg(feature = "json")]
+    (r#"{ "port": "3000", "debug": "true" }"#, Format::Json),
+    #[cfg(feature = "yaml")]
+    ("port: \"3000\"\ndebug: \"true\"", Format::Yaml),
+  ];
+
+  for (code, format) in sources {
+    let mut loader = ConfigLoader::<Conf>::new();
+    loader.code(code, format).unwrap();
+    let config = loader.finish().unwrap();
+    assert_eq!(
+      config,
+      Conf {
+        port: 3000,
+        debug: Some(true)
+      }
+    );
+  }
+
+  #[cfg(feature = "toml")]
+  {
+    let mut loader = ConfigLoader::<Conf>::new();
+    loader.code("port = 3000", Format::Toml).unwrap();
+    let config = loader.finish().unwrap();
+    assert_eq!(
+      config,
+      Conf {
+        port: 3000,
+        debug: None
+      }
+    );
+
+    let mut loader = ConfigLoader::<Conf>::new();
+    loader.code("port = \"not-a-port\"", Format::Toml).unwrap_err();
+  }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_finish_by_ref_and_keep_adding_stages() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(partial_derive(Clone))]
+  struct Conf {
+    port: u16,
+    #[config(nested)]
+    db: Db,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(partial_derive(Clone))]
+  struct Db {
+    url: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.finish_ref().unwrap_err();
+
+  loader.code("port = 3000", Format::Toml).unwrap();
+  let config = loader.finish_ref().unwrap();
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.db.url, None);
+
+  loader
+    .code("port = 3001\n[db]\nurl = \"postgres://localhost\"", Format::Toml)
+    .unwrap();
+  let config = loader.finish_ref().unwrap();
+  assert_eq!(config.port, 3001);
+  assert_eq!(config.db.url.as_deref(), Some("postgres://localhost"));
+
+  assert_eq!(loader.layers_applied(), 2);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_derive_generic_configs() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Component<T: Config> {
+    name: String,
+    #[config(nested)]
+    inner: T,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Service<T>
+  where
+    T: Config,
+  {
+    #[config(nested)]
+    component: Component<T>,
+    #[config(nested)]
+    backup: Option<T>,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Db {
+    url: String,
+  }
+
+  let mut loader = ConfigLoader::<Service<Db>>::new();
+  loader
+    .code(
+      r#"
+        [component]
+        name = "db"
+
+        [component.inner]
+        url = "postgres://localhost"
+      "#,
+      Format::Toml,
+    )
+    .unwrap();
+
+  let partial = format!("{:?}", loader.partial_state());
+  assert!(partial.contains("postgres://localhost"));
+
+  let config = loader.finish().unwrap();
+  assert_eq!(
+    config,
+    Service {
+      component: Component {
+        name: "db".into(),
+        inner: Db {
+          url: "postgres://localhost".into()
+        }
+      },
+      backup: None,
+    }
+  );
+
+  let mut loader = ConfigLoader::<Service<Db>>::new();
+  loader
+    .code("[component]\nname = \"db\"", Format::Toml)
+    .unwrap();
+  let err = loader.finish().unwrap_err();
+  assert_eq!(err.missing_properties().unwrap(), ["component.inner.url"]);
+}
+
+#[cfg(not(feature = "yaml"))]
+#[test]
+fn should_error_with_unsupported_format_for_disabled_formats() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[allow(unused)]
+    port: Option<u16>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.code_str("port: 3000", "yml").unwrap_err();
+  match &err {
+    metre::Error::UnsupportedFormat { format } => assert_eq!(format, "yaml"),
+    _ => panic!("expected UnsupportedFormat error, got {err:?}"),
+  }
+
+  let err = loader.code_str("port = 3000", "ini").unwrap_err();
+  assert!(matches!(err, metre::Error::ParseFormat(_)));
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_load_env_from_several_providers() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    host: String,
+  }
+
+  let mut defaults = HashMap::new();
+  defaults.insert("PORT", "3000");
+  defaults.insert("HOST", "localhost");
+
+  let mut overrides = std::collections::BTreeMap::new();
+  overrides.insert(String::from("PORT"), String::from("4000"));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_providers(&[&defaults, &overrides], None).unwrap();
+  assert_eq!(loader.layers_applied(), 2);
+
+  let config = loader.finish().unwrap();
+  assert_eq!(
+    config,
+    Conf {
+      port: 4000,
+      host: "localhost".into()
+    }
+  );
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_trim_env_values_with_trim_env() {
+  #[derive(Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+  enum Mode {
+    Dev,
+    Prod,
+  }
+
+  impl std::str::FromStr for Mode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+        "dev" => Ok(Mode::Dev),
+        "prod" => Ok(Mode::Prod),
+        _ => Err(format!("invalid mode {s:?}")),
+      }
+    }
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_prefix = "{}APP_", trim_env)]
+  struct Conf {
+    mode: Mode,
+    port: u16,
+    #[config(trim_env = false)]
+    banner: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_MODE", " prod ");
+  env.insert("APP_PORT", "3000\n");
+  env.insert("APP_BANNER", "  hello  ");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      mode: Mode::Prod,
+      port: 3000,
+      banner: "  hello  ".into(),
+    }
+  );
+
+  #[derive(Config, Debug)]
+  #[config(env_prefix = "{}APP_")]
+  struct Untrimmed {
+    #[allow(unused)]
+    mode: Mode,
+  }
+
+  let mut loader = ConfigLoader::<Untrimmed>::new();
+  loader.env_with_provider(&env).unwrap_err();
+}
+
+#[cfg(all(feature = "json", feature = "toml"))]
+#[test]
+fn should_create_a_loader_from_a_serialized_partial() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    host: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 3000", Format::Toml).unwrap();
+  let saved = serde_json::to_string(loader.partial_state()).unwrap();
+
+  let partial: PartialConf = serde_json::from_str(&saved).unwrap();
+  let mut loader = ConfigLoader::<Conf>::from_partial(partial);
+  assert_eq!(loader.layers_applied(), 1);
+
+  loader.code("host = \"localhost\"", Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      host: "localhost".into()
+    }
+  );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_check_required_fields_with_requires() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    port: u16,
+    #[config(requires = "tls_key")]
+    tls_cert: Option<String>,
+    tls_key: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 443\ntls_cert = \"cert.pem\"", Format::Toml).unwrap();
+  let err = loader.finish().unwrap_err();
+  assert_eq!(err.missing_properties().unwrap(), ["tls_key"]);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      "port = 443\ntls_cert = \"cert.pem\"\ntls_key = \"key.pem\"",
+      Format::Toml,
+    )
+    .unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.tls_key.as_deref(), Some("key.pem"));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 80", Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.tls_cert, None);
+  assert_eq!(config.tls_key, None);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_check_conflicting_fields_with_conflicts_with() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Conf {
+    #[config(nested)]
+    db: Db,
+  }
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  struct Db {
+    #[config(conflicts_with = "password_file")]
+    password: Option<String>,
+    password_file: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(
+      "[db]\npassword = \"secret\"\npassword_file = \"/run/secrets/db\"",
+      Format::Toml,
+    )
+    .unwrap();
+  let err = loader.finish().unwrap_err();
+  match &err {
+    metre::Error::FromPartial(e) => {
+      assert!(e.missing_properties.is_empty());
+      assert_eq!(
+        e.conflicting_properties,
+        [(String::from("db.password"), String::from("db.password_file"))]
+      );
+    }
+    _ => panic!("expected FromPartial error, got {err:?}"),
+  }
+  assert!(err.to_string().contains("conflicting properties"));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("[db]\npassword = \"secret\"", Format::Toml).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.db.password.as_deref(), Some("secret"));
+  assert_eq!(config.db.password_file, None);
+}
+
+#[cfg(all(feature = "env", feature = "toml"))]
+#[test]
+fn should_not_report_missing_properties_for_conflicts_only() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    #[config(conflicts_with = "password_file")]
+    password: Option<String>,
+    password_file: Option<String>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code("password = \"a\"\npassword_file = \"b\"", Format::Toml)
+    .unwrap();
+  loader.verbose_missing();
+  let err = loader.finish().unwrap_err();
+
+  assert!(matches!(err, metre::Error::MissingPropertiesVerbose { .. }));
+  assert!(!err.is_missing_properties());
+  assert_eq!(err.missing_properties(), None);
+
+  let message = err.to_string();
+  assert!(message.contains("conflicting properties"));
+  assert!(message.contains("password_file"));
+  assert!(!message.contains("missing properties"));
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_keep_parse_env_errors_as_source() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: Option<u16>,
+    #[config(parse_env = metre::parse::bounded_int::<u16>)]
+    other_port: Option<u16>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("PORT", "not-a-port");
+
+  let err = PartialConf::from_env_with_provider(&env).unwrap_err();
+  assert_eq!(err.key, "PORT");
+  let source = err.source.as_ref().unwrap();
+  assert!(source.downcast_ref::<std::num::ParseIntError>().is_some());
+  assert!(std::error::Error::source(&err).is_some());
+
+  let mut env = HashMap::new();
+  env.insert("OTHER_PORT", "not-a-port");
+
+  let err = PartialConf::from_env_with_provider(&env).unwrap_err();
+  assert_eq!(err.key, "OTHER_PORT");
+  assert!(err.source.is_none());
+}
+
+#[test]
+fn should_materialize_optional_nested_defaults_with_default_nested() {
+  #[derive(Config, Debug, PartialEq)]
+  struct Server {
+    #[config(default = 3000)]
+    port: u16,
+    host: Option<String>,
+  }
+
+  #[derive(Config, Debug, PartialEq)]
+  struct Tls {
+    cert: Option<String>,
+  }
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(nested, default_nested)]
+    server: Option<Server>,
+    #[config(nested, default_nested)]
+    tls: Option<Tls>,
+    #[config(nested)]
+    plain_tls: Option<Tls>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(config.server, Some(Server { port: 3000, host: None }));
+  assert_eq!(config.tls, Some(Tls { cert: None }));
+  assert_eq!(config.plain_tls, None);
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  #[config(default_nested)]
+  struct Container {
+    #[config(nested)]
+    server: Option<Conf>,
+    #[config(nested, default_nested = false)]
+    tls: Option<Tls>,
+  }
+
+  let defaults = PartialContainer::defaults();
+  assert!(defaults.server.is_some());
+  assert!(defaults.tls.is_none());
+}
+
+#[cfg(all(feature = "miette", feature = "env"))]
+#[test]
+fn should_finish_or_report_missing_properties() {
+  use miette::Diagnostic;
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    url: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    #[config(skip_env)]
+    host: String,
+    #[config(nested)]
+    db: Db,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_prefix(&HashMap::<String, String>::new(), "APP_").unwrap();
+  let report = loader.finish_or_report().unwrap_err();
+
+  assert_eq!(report.missing.len(), 3);
+  assert_eq!(report.missing[0].property, "port");
+  assert_eq!(report.missing[0].env_key.as_deref(), Some("APP_PORT"));
+  assert_eq!(report.missing[1].env_key, None);
+  assert_eq!(report.missing[2].property, "db.url");
+  assert_eq!(report.missing[2].env_key.as_deref(), Some("APP_DB_URL"));
+  assert_eq!(report.related().unwrap().count(), 3);
+  assert_eq!(
+    report.missing[0].help().unwrap().to_string(),
+    "add `port` to a config file or set the `APP_PORT` env variable"
+  );
+
+  let mut rendered = String::new();
+  miette::NarratableReportHandler::new()
+    .render_report(&mut rendered, &report)
+    .unwrap();
+  assert!(rendered.contains("the config is missing 3 required properties"));
+  assert!(rendered.contains("APP_DB_URL"));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = \"not a port\"", Format::Toml).unwrap_err();
+  loader.code("port = 3000\nhost = \"localhost\"\n[db]\nurl = \"db\"", Format::Toml).unwrap();
+  let config = loader.finish_or_report().unwrap();
+  assert_eq!(config.port, 3000);
+}
+
+const DEFAULT_PORT: u16 = 3000;
+
+mod defaults {
+  pub const WORKERS: usize = 4;
+
+  pub fn host() -> String {
+    String::from("localhost")
+  }
+}
+
+#[test]
+fn should_apply_defaults_referencing_constants_and_functions() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(default = crate::DEFAULT_PORT)]
+    port: u16,
+    #[config(default = DEFAULT_PORT + 1)]
+    admin_port: u16,
+    #[config(default = defaults::WORKERS * 2)]
+    workers: usize,
+    #[config(default = defaults::host())]
+    host: String,
+    #[config(default = defaults::host())]
+    admin_host: Option<String>,
+    #[config(skip, default = crate::DEFAULT_PORT - 1)]
+    internal_port: u16,
+  }
+
+  let defaults = PartialConf::defaults();
+  assert_eq!(defaults.port, Some(3000));
+  assert_eq!(defaults.admin_port, Some(3001));
+  assert_eq!(defaults.workers, Some(8));
+  assert_eq!(defaults.host.as_deref(), Some("localhost"));
+  assert_eq!(defaults.admin_host, Some(Some(String::from("localhost"))));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.admin_port, 3001);
+  assert_eq!(config.workers, 8);
+  assert_eq!(config.host, "localhost");
+  assert_eq!(config.admin_host.as_deref(), Some("localhost"));
+  assert_eq!(config.internal_port, 2999);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_strip_prefix_with_prefix_strip_env() {
+  use metre::{EnvProvider, PrefixStripEnv};
+
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_prefix = "{}APP_", env_strict)]
+  struct Conf {
+    port: u16,
+    db_password: String,
+  }
+
+  let mut secrets = HashMap::new();
+  secrets.insert("PORT", "3000");
+  secrets.insert("DB_PASSWORD", "secret");
+
+  let env = PrefixStripEnv::new("APP_", secrets);
+
+  assert_eq!(env.get("APP_PORT").unwrap().as_deref(), Some("3000"));
+  assert_eq!(env.get("PORT").unwrap(), None);
+
+  let mut keys = env.keys().unwrap();
+  keys.sort();
+  assert_eq!(keys, ["APP_DB_PASSWORD", "APP_PORT"]);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+
+  assert_eq!(
+    config,
+    Conf {
+      port: 3000,
+      db_password: "secret".into(),
+    }
+  );
+}
+
+#[cfg(all(feature = "url-blocking", feature = "json"))]
+#[test]
+fn should_return_url_meta() {
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+  }
+
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let server = std::thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut buf = [0; 1024];
+    let _ = stream.read(&mut buf).unwrap();
+    let body = r#"{ "port": 3000 }"#;
+    write!(
+      stream,
+      "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+      body.len(),
+      body
+    )
+    .unwrap();
+  });
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let (_, meta) = loader
+    .url_with_meta(&format!("http://{addr}/config.json"), Format::Json)
+    .unwrap();
+  server.join().unwrap();
+
+  assert_eq!(meta.status, 200);
+  assert_eq!(meta.content_length, Some(16));
+  assert_eq!(meta.body_length, 16);
+  assert_eq!(loader.finish().unwrap().port, 3000);
+}
+
+#[cfg(all(feature = "env", feature = "toml"))]
+#[test]
+fn should_refresh_env_over_the_current_state() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+    host: String,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_PORT", "3000");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 80\nhost = \"localhost\"", Format::Toml).unwrap();
+  loader.env_with_provider_and_prefix(&env, "APP_").unwrap();
+  assert_eq!(loader.partial_state().port, Some(3000));
+
+  env.insert("APP_PORT", "4000");
+  env.insert("APP_HOST", "example.com");
+  // unprefixed keys are not read, the prefix of the last env stage is reused
+  env.insert("PORT", "5000");
+  loader.refresh_env_with_provider(&env).unwrap();
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 4000);
+  assert_eq!(config.host, "example.com");
+}
+
+#[test]
+fn should_list_format_extensions() {
+  #[cfg(feature = "json")]
+  assert_eq!(Format::Json.extensions(), ["json"]);
+  #[cfg(feature = "jsonc")]
+  assert_eq!(Format::Jsonc.extensions(), ["jsonc"]);
+  #[cfg(feature = "toml")]
+  assert_eq!(Format::Toml.extensions(), ["toml"]);
+  #[cfg(feature = "yaml")]
+  {
+    assert_eq!(Format::Yaml.extensions(), ["yaml", "yml"]);
+    // every extension parses back to the same format
+    for ext in Format::Yaml.extensions() {
+      assert_eq!(ext.parse::<Format>().unwrap(), Format::Yaml);
+    }
+  }
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_read_plain_keys_with_empty_env_prefix() {
+  #[derive(Config, Debug)]
+  struct Db {
+    url: String,
+  }
+
+  #[derive(Config, Debug)]
+  struct Log {
+    level: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[config(env_prefix = "")]
+  struct Conf {
+    port: u16,
+    #[config(nested)]
+    db: Db,
+    #[config(nested, flatten)]
+    log: Log,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("PORT", "3000");
+  env.insert("DB_URL", "postgres://");
+  env.insert("LEVEL", "debug");
+
+  // the loader prefix is ignored as the format has no placeholder
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider_and_prefix(&env, "APP_").unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.db.url, "postgres://");
+  assert_eq!(config.log.level, "debug");
+
+  let mut keys = PartialConf::env_keys_with_optional_prefix(Some("APP_"));
+  keys.sort();
+  assert_eq!(keys, ["DB_URL", "LEVEL", "PORT"]);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_skip_conditional_stages_with_code_if_and_file_if() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+  }
+
+  let path = std::env::temp_dir()
+    .as_path()
+    .join("metre-test-config-if.toml");
+
+  std::fs::write(&path, "port = 4000").unwrap();
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code("port = 3000", Format::Toml)
+    .unwrap()
+    .code_if(false, "port = 3001", Format::Toml)
+    .unwrap()
+    // the file is not read at all if the condition is false
+    .file_if(false, "metre-missing-file.toml", Format::Toml)
+    .unwrap();
+
+  assert_eq!(loader.layers().len(), 1);
+  assert_eq!(loader.partial_state().port, Some(3000));
+
+  loader
+    .code_if(true, "port = 3002", Format::Toml)
+    .unwrap()
+    .file_if(true, &path, Format::Toml)
+    .unwrap();
+
+  assert_eq!(loader.layers().len(), 3);
+  assert_eq!(loader.finish().unwrap().port, 4000);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_join_sub_paths_of_custom_merge_errors() {
+  use metre::error::MergeError;
+
+  // limits can only be raised
+  fn merge_limits(
+    left: &mut Option<HashMap<String, u32>>,
+    right: Option<HashMap<String, u32>>,
+  ) -> Result<(), MergeError> {
+    let Some(right) = right else {
+      return Ok(());
+    };
+
+    let left = left.get_or_insert_with(HashMap::new);
+
+    for (key, value) in right {
+      if left.get(&key).is_some_and(|prev| value < *prev) {
+        return Err(MergeError::new(key, "limits can only be raised"));
+      }
+      left.insert(key, value);
+    }
+
+    Ok(())
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Server {
+    #[config(merge = merge_limits, skip_env)]
+    limits: HashMap<String, u32>,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    #[config(nested)]
+    server: Server,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("[server.limits]\nconnections = 10", Format::Toml).unwrap();
+  let err = loader.code("[server.limits]\nconnections = 5", Format::Toml).unwrap_err();
+
+  match err {
+    metre::Error::Merge(e) => {
+      assert_eq!(e.field, "server.limits.connections");
+      assert_eq!(e.message, "limits can only be raised");
+      assert!(e.source.is_none());
+    }
+    _ => panic!("expected Merge error, got {err:?}"),
+  }
+}
+
+#[test]
+fn should_extend_the_loader_with_partials() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+    host: String,
+    workers: Option<usize>,
+  }
+
+  let partials = vec![
+    PartialConf {
+      port: Some(3000),
+      host: Some(String::from("localhost")),
+      ..Default::default()
+    },
+    PartialConf {
+      port: Some(3001),
+      ..Default::default()
+    },
+    PartialConf {
+      workers: Some(Some(4)),
+      ..Default::default()
+    },
+  ];
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.extend(partials).unwrap();
+  assert_eq!(loader.layers().len(), 3);
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 3001);
+  assert_eq!(config.host, "localhost");
+  assert_eq!(config.workers, Some(4));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_profile_files() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+    host: String,
+  }
+
+  let dir = std::env::temp_dir().as_path().join("metre-test-profiles");
+  std::fs::create_dir_all(&dir).unwrap();
+  std::fs::write(dir.join("default.toml"), "port = 3000\nhost = \"localhost\"").unwrap();
+  std::fs::write(dir.join("prod.toml"), "host = \"example.com\"").unwrap();
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.profile(&dir, "prod", Format::Toml).unwrap();
+  assert_eq!(loader.layers().len(), 2);
+  let config = loader.finish().unwrap();
+  assert_eq!(config.port, 3000);
+  assert_eq!(config.host, "example.com");
+
+  // a missing profile file is skipped
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.profile(&dir, "staging", Format::Toml).unwrap();
+  assert_eq!(loader.layers().len(), 1);
+  assert_eq!(loader.finish().unwrap().host, "localhost");
+
+  // the default file is required
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.profile(dir.join("missing"), "prod", Format::Toml).unwrap_err();
+  assert!(err.as_io().is_some());
+}
+
+#[cfg(all(feature = "env", feature = "yaml"))]
+#[test]
+fn should_load_simple_enums_from_files_and_env() {
+  #[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+  #[serde(rename_all = "lowercase")]
+  enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+  }
+
+  impl std::str::FromStr for LogLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+      match s {
+        "debug" => Ok(LogLevel::Debug),
+        "info" => Ok(LogLevel::Info),
+        "warn" => Ok(LogLevel::Warn),
+        _ => Err(format!("unknown log level {s}")),
+      }
+    }
+  }
+
+  #[derive(Config, Debug)]
+  #[config(env_prefix = "{}APP_")]
+  struct Conf {
+    level: LogLevel,
+    fallback_level: Option<LogLevel>,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("level: debug\nfallback_level: warn", Format::Yaml).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.level, LogLevel::Debug);
+  assert_eq!(config.fallback_level, Some(LogLevel::Warn));
+
+  let mut env = HashMap::new();
+  env.insert("APP_LEVEL", "info");
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.level, LogLevel::Info);
+  assert_eq!(config.fallback_level, None);
+
+  let mut env = HashMap::new();
+  env.insert("APP_FALLBACK_LEVEL", "trace");
+  let err = PartialConf::from_env_with_provider(&env).unwrap_err();
+  assert_eq!(err.key, "APP_FALLBACK_LEVEL");
+  assert_eq!(err.message, "unknown log level trace");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader.code("level: trace", Format::Yaml).unwrap_err();
+  assert_eq!(err.field_path(), Some("level"));
+}
+
+#[cfg(all(feature = "toml", feature = "json"))]
+#[test]
+fn should_take_the_partial_out_of_the_loader() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    host: String,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("port = 3000", Format::Toml).unwrap();
+  loader.code(r#"{ "port": 3001 }"#, Format::Json).unwrap();
+
+  let partial = loader.take_partial();
+  assert_eq!(partial.port, Some(3001));
+  assert_eq!(serde_json::to_string(&partial).unwrap(), r#"{"port":3001}"#);
+}
+
+#[cfg(all(feature = "env", feature = "toml"))]
+#[test]
+fn should_append_env_lists_to_file_lists() {
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(merge = metre::merge::append_vec, parse_env = metre::parse::comma_separated::<String>)]
+    hosts: Vec<String>,
+  }
+
+  let mut first = HashMap::new();
+  first.insert("HOSTS", "c,d");
+
+  let mut second = HashMap::new();
+  second.insert("HOSTS", "e");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code(r#"hosts = ["a", "b"]"#, Format::Toml).unwrap();
+  loader.env_with_providers(&[&first, &second], None).unwrap();
+  // a file stage after the env stages is appended too
+  loader.code(r#"hosts = ["f"]"#, Format::Toml).unwrap();
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.hosts, ["a", "b", "c", "d", "e", "f"]);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_check_env_for_missing_properties() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    url: String,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    #[config(default = String::from("localhost"))]
+    host: String,
+    #[config(nested)]
+    db: Db,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_PORT", "3000");
+
+  let err = ConfigLoader::<Conf>::check_env_with_provider(&env, Some("APP_")).unwrap_err();
+  assert_eq!(err.missing_properties().unwrap(), ["db.url"]);
+
+  env.insert("APP_DB_URL", "postgres://");
+  ConfigLoader::<Conf>::check_env_with_provider(&env, Some("APP_")).unwrap();
+
+  env.insert("APP_PORT", "not-a-port");
+  let err = ConfigLoader::<Conf>::check_env_with_provider(&env, Some("APP_")).unwrap_err();
+  assert_eq!(err.missing_properties(), None);
+  match err {
+    metre::error::CheckEnvError::FromEnv(e) => {
+      assert_eq!(e.key, "APP_PORT");
+      assert_eq!(e.field, "port");
+    }
+    _ => panic!("expected FromEnv error, got {err:?}"),
+  }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_merge_toml_sections_and_dotted_keys() {
+  #[derive(Config, Debug)]
+  struct Nested {
+    port: u16,
+    other: u16,
+    host: String,
+  }
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    #[config(nested)]
+    nested: Nested,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.code("[nested]\nport = 1", Format::Toml).unwrap();
+  loader.code("nested.other = 2", Format::Toml).unwrap();
+  loader.code("nested = { host = \"localhost\" }", Format::Toml).unwrap();
+
+  let config = loader.finish().unwrap();
+  assert_eq!(config.nested.port, 1);
+  assert_eq!(config.nested.other, 2);
+  assert_eq!(config.nested.host, "localhost");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_report_the_label_of_labeled_code() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .code_labeled(r#"port = "3000""#, Format::Toml, "cli overrides")
+    .unwrap_err();
+
+  assert!(err.to_string().contains("cli overrides"));
+
+  loader.code_labeled("port = 3000", Format::Toml, "cli overrides").unwrap();
+  assert_eq!(loader.layers()[0].location, metre::LoadLocation::named("cli overrides"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_from_a_reader() {
+  use std::io::Cursor;
+
+  #[derive(Config, Debug)]
+  struct Conf {
+    port: u16,
+  }
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.reader(Cursor::new(""), Format::Toml, "stdin").unwrap();
+  loader.reader(Cursor::new("port = 3000"), Format::Toml, "stdin").unwrap();
+  assert!(loader.layers()[0].is_empty);
+  assert_eq!(loader.layers()[1].location, metre::LoadLocation::named("stdin"));
+  assert_eq!(loader.finish().unwrap().port, 3000);
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  let err = loader
+    .reader(Cursor::new(vec![0xff, 0xfe]), Format::Toml, "stdin")
+    .unwrap_err();
+  assert_eq!(err.as_io().unwrap().kind(), std::io::ErrorKind::InvalidData);
+  assert!(err.to_string().contains("stdin"));
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_set_flags_by_presence_with_env_bool_presence() {
+  #[derive(Config, Debug)]
+  #[config(env_prefix = "{}APP_")]
+  struct Conf {
+    #[config(env_bool_presence, default = false)]
+    verbose: bool,
+    #[config(env_bool_presence)]
+    quiet: Option<bool>,
+  }
+
+  let mut env = HashMap::new();
+  env.insert("APP_VERBOSE", "");
+  env.insert("APP_QUIET", "no");
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  loader.env_with_provider(&env).unwrap();
+  let config = loader.finish().unwrap();
+  assert!(config.verbose);
+  assert_eq!(config.quiet, Some(true));
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader.defaults().unwrap();
+  loader.env_with_provider(&HashMap::<String, String>::new()).unwrap();
+  let config = loader.finish().unwrap();
+  assert!(!config.verbose);
+  assert_eq!(config.quiet, None);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn should_load_from_file_in_one_call() {
+  #[derive(Config, Debug, Eq, PartialEq)]
+  #[config(env_prefix = "{}METRE_TEST_FROM_FILE_")]
+  struct Conf {
+    #[config(default = 3000)]
+    port: u16,
+    host: String,
+  }
+
+  let path = std::env::temp_dir()
+    .as_path()
+    .join("metre-test-from-file.toml");
+
+  std::fs::write(&path, "host = \"localhost\"").unwrap();
+
+  let config = ConfigLoader::<Conf>::from_file(&path, Format::Toml).unwrap();
+  assert_eq!(config, Conf { port: 3000, host: "localhost".into() });
+
+  #[cfg(feature = "env")]
+  {
+    std::env::set_var("METRE_TEST_FROM_FILE_HOST", "example.com");
+    let config = ConfigLoader::<Conf>::from_file_with_env(&path, Format::Toml).unwrap();
+    assert_eq!(config, Conf { port: 3000, host: "example.com".into() });
+  }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn should_rename_properties_like_serde_rename_all() {
+  macro_rules! assert_serde_names {
+    ($case:literal) => {{
+      #[derive(Config, Debug)]
+      #[allow(unused)]
+      #[config(rename_all = $case)]
+      struct Conf {
+        http2_port: u16,
+        ipv4_addr: String,
+        tls_v1_3: bool,
+        max_connections: u32,
+      }
+
+      let partial = PartialConf {
+        http2_port: Some(80),
+        ipv4_addr: Some(String::from("127.0.0.1")),
+        tls_v1_3: Some(true),
+        max_connections: Some(10),
+      };
+
+      // the names used by serde in the serialized partial
+      let value = serde_json::to_value(&partial).unwrap();
+      let mut serde_names = value.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+      serde_names.sort();
+
+      let mut names = PartialConf::default().list_missing_properties();
+      names.sort();
+
+      assert_eq!(names, serde_names, "rename_all = {}", $case);
+      names
+    }};
+  }
+
+  assert_eq!(
+    assert_serde_names!("snake_case"),
+    ["http2_port", "ipv4_addr", "max_connections", "tls_v1_3"]
+  );
+  assert_eq!(
+    assert_serde_names!("camelCase"),
+    ["http2Port", "ipv4Addr", "maxConnections", "tlsV13"]
+  );
+  assert_eq!(
+    assert_serde_names!("PascalCase"),
+    ["Http2Port", "Ipv4Addr", "MaxConnections", "TlsV13"]
+  );
+  assert_eq!(
+    assert_serde_names!("kebab-case"),
+    ["http2-port", "ipv4-addr", "max-connections", "tls-v1-3"]
+  );
+  assert_eq!(
+    assert_serde_names!("SCREAMING_SNAKE_CASE"),
+    ["HTTP2_PORT", "IPV4_ADDR", "MAX_CONNECTIONS", "TLS_V1_3"]
+  );
+  assert_eq!(
+    assert_serde_names!("SCREAMING-KEBAB-CASE"),
+    ["HTTP2-PORT", "IPV4-ADDR", "MAX-CONNECTIONS", "TLS-V1-3"]
+  );
+  assert_serde_names!("lowercase");
+  assert_serde_names!("UPPERCASE");
+}
+
+#[cfg(feature = "json")]
+#[test]
+#[allow(non_snake_case)]
+fn should_rename_screaming_kebab_case_like_serde() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  #[config(rename_all = "SCREAMING-KEBAB-CASE")]
+  struct Conf {
+    apiV2Url: String,
+    oauth2: bool,
+    http_port: u16,
+  }
+
+  let partial = PartialConf {
+    apiV2Url: Some(String::from("http://api")),
+    oauth2: Some(true),
+    http_port: Some(80),
+  };
+
+  assert_eq!(
+    serde_json::to_string(&partial).unwrap(),
+    r#"{"APIV2URL":"http://api","OAUTH2":true,"HTTP-PORT":80}"#
+  );
+  assert_eq!(
+    PartialConf::default().list_missing_properties(),
+    ["APIV2URL", "OAUTH2", "HTTP-PORT"]
+  );
+
+  let mut loader = ConfigLoader::<Conf>::new();
+  loader
+    .code(r#"{ "APIV2URL": "http://api", "OAUTH2": true, "HTTP-PORT": 80 }"#, Format::Json)
+    .unwrap();
+  let config = loader.finish().unwrap();
+  assert_eq!(config.apiV2Url, "http://api");
+  assert_eq!(config.http_port, 80);
+}
+
+#[cfg(feature = "env")]
+#[test]
+fn should_list_expected_env_keys() {
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Db {
+    url: String,
+    #[config(skip_env)]
+    pool: Option<u32>,
+  }
+
+  #[derive(Config, Debug)]
+  #[allow(unused)]
+  struct Conf {
+    port: u16,
+    #[config(skip_env)]
+    secret: Option<String>,
+    #[config(nested)]
+    db: Db,
+  }
+
+  assert_eq!(
+    ConfigLoader::<Conf>::expected_env_keys(Some("APP_")),
+    ["APP_PORT", "APP_DB_URL"]
+  );
+  assert_eq!(ConfigLoader::<Conf>::expected_env_keys(None), ["PORT", "DB_URL"]);
+}